@@ -0,0 +1,310 @@
+//! Precomputed attack tables
+//!
+//! Attacks for the leaping pieces and pawns only depend on the square they
+//! attack from, so they're calculated once at compile time from the
+//! bitboard shift operations. Sliding attacks also depend on which squares
+//! are occupied, and are looked up in the magic bitboard tables, or in PEXT
+//! indexed tables when the `bmi2` feature is enabled and the CPU supports it.
+
+use crate::bitboard::{BitBoard, NO_SQUARES};
+use crate::board::Color;
+use crate::magic;
+#[cfg(all(feature = "bmi2", target_arch = "x86_64"))]
+use crate::pext;
+use crate::square::Square;
+
+/// A function shifting every square on a board one step in some direction
+pub type Direction = fn(&BitBoard) -> BitBoard;
+
+pub const ROOK_DIRECTIONS: [Direction; 4] = [
+    BitBoard::shift_north,
+    BitBoard::shift_east,
+    BitBoard::shift_south,
+    BitBoard::shift_west,
+];
+
+pub const BISHOP_DIRECTIONS: [Direction; 4] = [
+    BitBoard::shift_northeast,
+    BitBoard::shift_southeast,
+    BitBoard::shift_southwest,
+    BitBoard::shift_northwest,
+];
+
+const KNIGHT_ATTACKS: [BitBoard; 64] = knight_table();
+const KING_ATTACKS: [BitBoard; 64] = king_table();
+const WHITE_PAWN_ATTACKS: [BitBoard; 64] = pawn_table(Color::White);
+const BLACK_PAWN_ATTACKS: [BitBoard; 64] = pawn_table(Color::Black);
+
+/// Squares attacked by a knight on the given square
+pub const fn knight_attacks(square: &Square) -> BitBoard {
+    KNIGHT_ATTACKS[*square as usize]
+}
+
+/// Squares attacked by a king on the given square
+pub const fn king_attacks(square: &Square) -> BitBoard {
+    KING_ATTACKS[*square as usize]
+}
+
+/// Squares attacked by a pawn of the given color on the given square
+pub const fn pawn_attacks(square: &Square, color: Color) -> BitBoard {
+    match color {
+        Color::White => WHITE_PAWN_ATTACKS[*square as usize],
+        Color::Black => BLACK_PAWN_ATTACKS[*square as usize],
+    }
+}
+
+/// Squares attacked by a rook on the given square
+pub fn rook_attacks(square: &Square, occupancy: &BitBoard) -> BitBoard {
+    #[cfg(all(feature = "bmi2", target_arch = "x86_64"))]
+    if let Some(tables) = pext::tables() {
+        return tables.rook_attacks(square, occupancy);
+    }
+    magic::tables().rook_attacks(square, occupancy)
+}
+
+/// Squares attacked by a bishop on the given square
+pub fn bishop_attacks(square: &Square, occupancy: &BitBoard) -> BitBoard {
+    #[cfg(all(feature = "bmi2", target_arch = "x86_64"))]
+    if let Some(tables) = pext::tables() {
+        return tables.bishop_attacks(square, occupancy);
+    }
+    magic::tables().bishop_attacks(square, occupancy)
+}
+
+/// Squares attacked by a queen on the given square
+pub fn queen_attacks(square: &Square, occupancy: &BitBoard) -> BitBoard {
+    rook_attacks(square, occupancy).union(&bishop_attacks(square, occupancy))
+}
+
+/// Squares reachable by sliding from a square in the given directions
+///
+/// Each ray stops at (and includes) the first occupied square it reaches.
+/// This walks the rays one step at a time, so it's only used for building
+/// tables and other setup work.
+pub fn slide(from: &BitBoard, directions: &[Direction], occupied: &BitBoard) -> BitBoard {
+    directions.iter().fold(NO_SQUARES, |targets, shift| {
+        let mut targets = targets;
+        let mut ray = shift(from);
+        while !ray.is_empty() {
+            targets = targets.union(&ray);
+            if ray.intersects(occupied) {
+                break;
+            }
+            ray = shift(&ray);
+        }
+        targets
+    })
+}
+
+const fn knight_targets(from: &BitBoard) -> BitBoard {
+    from.shift_north()
+        .shift_northeast()
+        .union(&from.shift_north().shift_northwest())
+        .union(&from.shift_east().shift_northeast())
+        .union(&from.shift_east().shift_southeast())
+        .union(&from.shift_south().shift_southeast())
+        .union(&from.shift_south().shift_southwest())
+        .union(&from.shift_west().shift_southwest())
+        .union(&from.shift_west().shift_northwest())
+}
+
+const fn king_targets(from: &BitBoard) -> BitBoard {
+    from.shift_north()
+        .union(&from.shift_northeast())
+        .union(&from.shift_east())
+        .union(&from.shift_southeast())
+        .union(&from.shift_south())
+        .union(&from.shift_southwest())
+        .union(&from.shift_west())
+        .union(&from.shift_northwest())
+}
+
+const fn pawn_targets(from: &BitBoard, color: Color) -> BitBoard {
+    match color {
+        Color::White => from.shift_northeast().union(&from.shift_northwest()),
+        Color::Black => from.shift_southeast().union(&from.shift_southwest()),
+    }
+}
+
+const fn knight_table() -> [BitBoard; 64] {
+    let mut table = [NO_SQUARES; 64];
+    let mut idx = 0;
+    while idx < 64 {
+        table[idx] = knight_targets(&BitBoard::new(1 << idx));
+        idx += 1;
+    }
+    table
+}
+
+const fn king_table() -> [BitBoard; 64] {
+    let mut table = [NO_SQUARES; 64];
+    let mut idx = 0;
+    while idx < 64 {
+        table[idx] = king_targets(&BitBoard::new(1 << idx));
+        idx += 1;
+    }
+    table
+}
+
+const fn pawn_table(color: Color) -> [BitBoard; 64] {
+    let mut table = [NO_SQUARES; 64];
+    let mut idx = 0;
+    while idx < 64 {
+        table[idx] = pawn_targets(&BitBoard::new(1 << idx), color);
+        idx += 1;
+    }
+    table
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bitboard::{ALL_SQUARES, A_FILE, DIAGONAL_A1_H8, RANK_1};
+    use crate::square::SQUARES;
+
+    #[test]
+    fn test_knight_attacks() {
+        assert!(
+            knight_attacks(&Square::A1) == BitBoard::from_squares(&vec![Square::B3, Square::C2])
+        );
+        assert!(
+            knight_attacks(&Square::G7)
+                == BitBoard::from_squares(&vec![Square::E8, Square::E6, Square::F5, Square::H5])
+        );
+        assert!(knight_attacks(&Square::D4).count() == 8);
+    }
+
+    #[test]
+    fn test_king_attacks() {
+        assert!(
+            king_attacks(&Square::H8)
+                == BitBoard::from_squares(&vec![Square::G8, Square::G7, Square::H7])
+        );
+        assert!(king_attacks(&Square::E4).count() == 8);
+    }
+
+    #[test]
+    fn test_pawn_attacks() {
+        assert!(
+            pawn_attacks(&Square::E4, Color::White)
+                == BitBoard::from_squares(&vec![Square::D5, Square::F5])
+        );
+        assert!(
+            pawn_attacks(&Square::E4, Color::Black)
+                == BitBoard::from_squares(&vec![Square::D3, Square::F3])
+        );
+        assert!(pawn_attacks(&Square::A2, Color::White) == BitBoard::from_square(&Square::B3));
+        assert!(pawn_attacks(&Square::H7, Color::Black) == BitBoard::from_square(&Square::G6));
+        assert!(pawn_attacks(&Square::C8, Color::White).is_empty());
+    }
+
+    #[test]
+    fn test_rook_attacks() {
+        let occupancy =
+            BitBoard::from_squares(&vec![Square::D6, Square::F4, Square::B4, Square::D1]);
+        assert!(
+            rook_attacks(&Square::D4, &occupancy)
+                == BitBoard::from_squares(&vec![
+                    Square::D5,
+                    Square::D6,
+                    Square::E4,
+                    Square::F4,
+                    Square::D3,
+                    Square::D2,
+                    Square::D1,
+                    Square::C4,
+                    Square::B4,
+                ])
+        );
+        assert!(rook_attacks(&Square::A1, &NO_SQUARES) == A_FILE.union(&RANK_1).unset(&Square::A1));
+    }
+
+    #[test]
+    fn test_bishop_attacks() {
+        let occupancy = BitBoard::from_squares(&vec![Square::F6, Square::B2]);
+        assert!(
+            bishop_attacks(&Square::D4, &occupancy)
+                == BitBoard::from_squares(&vec![
+                    Square::E5,
+                    Square::F6,
+                    Square::E3,
+                    Square::F2,
+                    Square::G1,
+                    Square::C3,
+                    Square::B2,
+                    Square::C5,
+                    Square::B6,
+                    Square::A7,
+                ])
+        );
+        assert!(bishop_attacks(&Square::A1, &NO_SQUARES) == DIAGONAL_A1_H8.unset(&Square::A1));
+    }
+
+    #[test]
+    fn test_queen_attacks() {
+        assert!(queen_attacks(&Square::D4, &ALL_SQUARES).count() == 8);
+        assert!(queen_attacks(&Square::A8, &NO_SQUARES).count() == 21);
+    }
+
+    #[test]
+    fn test_sliding_attacks_match_slide() {
+        // A handful of scattered occupancies against every square
+        let occupancies = [
+            BitBoard::new(0x0000_0000_0000_0000),
+            BitBoard::new(0x0042_0010_2400_8100),
+            BitBoard::new(0x81a5_0c30_0300_c581),
+            BitBoard::new(0xffff_0000_0000_ffff),
+        ];
+        for square in SQUARES.iter() {
+            let from = BitBoard::from_square(square);
+            for occupancy in occupancies.iter() {
+                assert!(
+                    rook_attacks(square, occupancy) == slide(&from, &ROOK_DIRECTIONS, occupancy)
+                );
+                assert!(
+                    bishop_attacks(square, occupancy)
+                        == slide(&from, &BISHOP_DIRECTIONS, occupancy)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_fills_match_lookups() {
+        let occupancies = [
+            BitBoard::new(0x0000_0000_0000_0000),
+            BitBoard::new(0x0042_0010_2400_8100),
+            BitBoard::new(0x81a5_0c30_0300_c581),
+        ];
+        for occupancy in occupancies.iter() {
+            // Every occupied square as a slider at once, and each alone
+            let sliders = occupancy.occupied_squares();
+            let union = |attacks: fn(&Square, &BitBoard) -> BitBoard| {
+                sliders.iter().fold(NO_SQUARES, |all, square| {
+                    all.union(&attacks(square, occupancy))
+                })
+            };
+            assert!(occupancy.orthogonal_attacks(occupancy) == union(rook_attacks));
+            assert!(occupancy.diagonal_attacks(occupancy) == union(bishop_attacks));
+            for square in SQUARES.iter() {
+                let from = BitBoard::from_square(square);
+                assert!(from.orthogonal_attacks(occupancy) == rook_attacks(square, occupancy));
+                assert!(from.diagonal_attacks(occupancy) == bishop_attacks(square, occupancy));
+            }
+        }
+    }
+
+    #[test]
+    fn test_table_totals() {
+        let total = |attacks: fn(&Square) -> BitBoard| {
+            SQUARES
+                .iter()
+                .map(|square| attacks(square).count())
+                .sum::<u32>()
+        };
+        assert!(total(knight_attacks) == 336);
+        assert!(total(king_attacks) == 420);
+        assert!(total(|square| pawn_attacks(square, Color::White)) == 98);
+        assert!(total(|square| pawn_attacks(square, Color::Black)) == 98);
+    }
+}
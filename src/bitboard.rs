@@ -22,6 +22,13 @@ pub const NOT_RANK_1: BitBoard = RANK_1.complement();
 pub const RANK_8: BitBoard = BitBoard::new(0xFF00000000000000);
 pub const NOT_RANK_8: BitBoard = RANK_8.complement();
 
+pub const RANK_2: BitBoard = RANK_1.shift_north();
+pub const RANK_3: BitBoard = RANK_2.shift_north();
+pub const RANK_4: BitBoard = RANK_3.shift_north();
+pub const RANK_5: BitBoard = RANK_4.shift_north();
+pub const RANK_6: BitBoard = RANK_5.shift_north();
+pub const RANK_7: BitBoard = RANK_6.shift_north();
+
 pub const DIAGONAL_A1_H8: BitBoard = BitBoard::new(0x8040201008040201);
 pub const DIAGONAL_H1_A8: BitBoard = BitBoard::new(0x0102040810204080);
 
@@ -56,14 +63,16 @@ impl BitBoard {
         BitBoard { positions }
     }
     pub const fn from_square(square: &Square) -> Self {
-        BitBoard::new(SquarePosition::from_square(&square) as u64)
+        BitBoard::new(SquarePosition::from_square(square) as u64)
     }
+    #[allow(clippy::ptr_arg)]
     pub fn from_squares(squares: &Vec<Square>) -> Self {
         squares
             .iter()
-            .map(|square| BitBoard::from_square(square))
+            .map(BitBoard::from_square)
             .fold(NO_SQUARES, |result, board| result.union(&board))
     }
+    #[allow(clippy::ptr_arg)]
     pub fn from_boards<T: AsRef<Self>>(boards: &Vec<T>) -> Self {
         boards
             .iter()
@@ -75,10 +84,18 @@ impl BitBoard {
     pub const fn is_empty(&self) -> bool {
         self.positions == 0
     }
+    /// The number of occupied squares
+    pub const fn count(&self) -> u32 {
+        self.positions.count_ones()
+    }
+    /// The raw LRF positions of the board
+    pub const fn positions(&self) -> u64 {
+        self.positions
+    }
     pub fn occupied_squares(&self) -> Vec<Square> {
         SQUARES
             .iter()
-            .map(|s| *s)
+            .copied()
             .filter(|s| self.occupied(s))
             .collect()
     }
@@ -172,6 +189,100 @@ impl BitBoard {
         BitBoard::new(self.intersection(&NOT_H_FILE).positions >> (7 * shift.distance))
     }
 }
+/// Fill Functions
+///
+/// Kogge-Stone occluded fills, which slide every square on the board at
+/// once in one direction, stopping at occupied squares. These work on any
+/// number of sliders together, so e.g. all of one side's rooks' attacks can
+/// be found in one pass without any lookup tables.
+///
+/// A fill includes the starting squares and the empty squares reached, but
+/// not the blockers. The matching attack function shifts the fill one more
+/// step, which includes the blockers and drops the starting squares.
+impl BitBoard {
+    pub fn fill_east(&self, occupied: &Self) -> BitBoard {
+        self.occluded_fill(occupied, Self::shift_east_by)
+    }
+    pub fn fill_northeast(&self, occupied: &Self) -> BitBoard {
+        self.occluded_fill(occupied, Self::shift_northeast_by)
+    }
+    pub fn fill_north(&self, occupied: &Self) -> BitBoard {
+        self.occluded_fill(occupied, Self::shift_north_by)
+    }
+    pub fn fill_northwest(&self, occupied: &Self) -> BitBoard {
+        self.occluded_fill(occupied, Self::shift_northwest_by)
+    }
+    pub fn fill_west(&self, occupied: &Self) -> BitBoard {
+        self.occluded_fill(occupied, Self::shift_west_by)
+    }
+    pub fn fill_southwest(&self, occupied: &Self) -> BitBoard {
+        self.occluded_fill(occupied, Self::shift_southwest_by)
+    }
+    pub fn fill_south(&self, occupied: &Self) -> BitBoard {
+        self.occluded_fill(occupied, Self::shift_south_by)
+    }
+    pub fn fill_southeast(&self, occupied: &Self) -> BitBoard {
+        self.occluded_fill(occupied, Self::shift_southeast_by)
+    }
+
+    pub fn east_attacks(&self, occupied: &Self) -> BitBoard {
+        self.fill_east(occupied).shift_east()
+    }
+    pub fn northeast_attacks(&self, occupied: &Self) -> BitBoard {
+        self.fill_northeast(occupied).shift_northeast()
+    }
+    pub fn north_attacks(&self, occupied: &Self) -> BitBoard {
+        self.fill_north(occupied).shift_north()
+    }
+    pub fn northwest_attacks(&self, occupied: &Self) -> BitBoard {
+        self.fill_northwest(occupied).shift_northwest()
+    }
+    pub fn west_attacks(&self, occupied: &Self) -> BitBoard {
+        self.fill_west(occupied).shift_west()
+    }
+    pub fn southwest_attacks(&self, occupied: &Self) -> BitBoard {
+        self.fill_southwest(occupied).shift_southwest()
+    }
+    pub fn south_attacks(&self, occupied: &Self) -> BitBoard {
+        self.fill_south(occupied).shift_south()
+    }
+    pub fn southeast_attacks(&self, occupied: &Self) -> BitBoard {
+        self.fill_southeast(occupied).shift_southeast()
+    }
+
+    /// Squares attacked along ranks and files by sliders on every square
+    pub fn orthogonal_attacks(&self, occupied: &Self) -> BitBoard {
+        self.north_attacks(occupied)
+            .union(&self.east_attacks(occupied))
+            .union(&self.south_attacks(occupied))
+            .union(&self.west_attacks(occupied))
+    }
+    /// Squares attacked along diagonals by sliders on every square
+    pub fn diagonal_attacks(&self, occupied: &Self) -> BitBoard {
+        self.northeast_attacks(occupied)
+            .union(&self.southeast_attacks(occupied))
+            .union(&self.southwest_attacks(occupied))
+            .union(&self.northwest_attacks(occupied))
+    }
+
+    /// Fill through unoccupied squares, shifting one way by doubling steps
+    ///
+    /// The shift masks off the edge a square would wrap around from, and the
+    /// propagator only keeps squares with empty squares all the way back, so
+    /// a longer shift can't cross the edge either.
+    fn occluded_fill(&self, occupied: &Self, shift_by: fn(&Self, Shift) -> Self) -> Self {
+        let mut generator = *self;
+        let mut propagator = occupied.complement();
+        // Steps of one, two and four cover the seven squares to the edge
+        for distance in [1, 2, 4].iter() {
+            let shift = Shift::new(*distance);
+            generator = generator.union(&propagator.intersection(&shift_by(&generator, shift)));
+            propagator = propagator.intersection(&shift_by(&propagator, shift));
+        }
+        generator
+    }
+}
+
 /// Update methods
 impl BitBoard {
     /// Ensure a given square is set
@@ -381,6 +492,80 @@ mod test_bitboard {
         );
     }
 
+    #[test]
+    fn test_fill() {
+        let rook = BitBoard::from_square(&Square::D4);
+        let occupied = BitBoard::from_squares(&vec![Square::D7, Square::G4]);
+        assert!(
+            rook.fill_north(&occupied)
+                == BitBoard::from_squares(&vec![Square::D4, Square::D5, Square::D6])
+        );
+        assert!(
+            rook.north_attacks(&occupied)
+                == BitBoard::from_squares(&vec![Square::D5, Square::D6, Square::D7])
+        );
+        assert!(
+            rook.east_attacks(&occupied)
+                == BitBoard::from_squares(&vec![Square::E4, Square::F4, Square::G4])
+        );
+        assert!(rook.fill_south(&NO_SQUARES).count() == 4);
+        // Fills don't wrap around the edge of the board
+        assert!(H_FILE.east_attacks(&NO_SQUARES).is_empty());
+        assert!(A_FILE.fill_west(&NO_SQUARES) == A_FILE);
+        assert!(RANK_1.fill_north(&NO_SQUARES) == ALL_SQUARES);
+        assert!(
+            BitBoard::from_square(&Square::A1).northeast_attacks(&NO_SQUARES)
+                == DIAGONAL_A1_H8.unset(&Square::A1)
+        );
+        assert!(
+            BitBoard::from_square(&Square::H2).southwest_attacks(&NO_SQUARES)
+                == BitBoard::from_square(&Square::G1)
+        );
+    }
+
+    #[test]
+    fn test_setwise_attacks() {
+        let rooks = BitBoard::from_squares(&vec![Square::A1, Square::H8]);
+        let occupied =
+            BitBoard::from_squares(&vec![Square::A1, Square::H8, Square::A4, Square::E8]);
+        assert!(
+            rooks.orthogonal_attacks(&occupied)
+                == BitBoard::from_squares(&vec![
+                    Square::A2,
+                    Square::A3,
+                    Square::A4,
+                    Square::E8,
+                    Square::F8,
+                    Square::G8,
+                    Square::H7,
+                    Square::H6,
+                    Square::H5,
+                    Square::H4,
+                    Square::H3,
+                    Square::H2,
+                    Square::H1,
+                    Square::B1,
+                    Square::C1,
+                    Square::D1,
+                    Square::E1,
+                    Square::F1,
+                    Square::G1,
+                ])
+        );
+        let bishops = BitBoard::from_squares(&vec![Square::C1, Square::F1]);
+        assert!(
+            bishops.diagonal_attacks(&RANK_2)
+                == BitBoard::from_squares(&vec![Square::B2, Square::D2, Square::E2, Square::G2])
+        );
+    }
+
+    #[test]
+    fn test_intermediate_ranks() {
+        assert!(RANK_2 == BitBoard::new(0x000000000000FF00));
+        assert!(RANK_7 == BitBoard::new(0x00FF000000000000));
+        assert!(RANK_7.shift_north() == RANK_8);
+    }
+
     #[test]
     fn test_from_square() {
         assert!(BitBoard::from_square(&Square::A1).shift_south() == NO_SQUARES);
@@ -409,6 +594,13 @@ mod test_bitboard {
         )
     }
 
+    #[test]
+    fn test_count() {
+        assert!(NO_SQUARES.count() == 0);
+        assert!(RANK_1.count() == 8);
+        assert!(ALL_SQUARES.count() == 64);
+    }
+
     #[test]
     fn test_set() {
         assert!(NO_SQUARES.set(&Square::A1) == BitBoard::from_square(&Square::A1))
@@ -4,6 +4,8 @@ use colored::*;
 use std::fmt;
 
 use crate::bitboard::BitBoard;
+use crate::castling::CastleSide;
+use crate::moves::{Move, MoveKind};
 use crate::square::{Square, SquareColor, SQUARES};
 use crate::traits::{Movable, Occupied};
 
@@ -30,32 +32,6 @@ const STARTING_BLACK_KING: BitBoard = BitBoard::from_square(&Square::E8);
 // Precalculating starting board
 const STARTING_BOARD: Board = Board::fresh_game();
 
-pub struct MoveComponent<'a> {
-    square: &'a Square,
-    board: BitBoard,
-}
-impl<'a> MoveComponent<'a> {
-    pub const fn new(square: &'a Square) -> Self {
-        Self {
-            square,
-            board: BitBoard::from_square(square),
-        }
-    }
-}
-
-pub struct Move<'a> {
-    from: MoveComponent<'a>,
-    to: MoveComponent<'a>,
-}
-impl<'a> Move<'a> {
-    pub const fn new(from: &'a Square, to: &'a Square) -> Self {
-        Self {
-            from: MoveComponent::new(from),
-            to: MoveComponent::new(to),
-        }
-    }
-}
-
 struct Stash {
     all_pieces: Option<BitBoard>,
 }
@@ -69,13 +45,22 @@ impl Stash {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Color {
     White,
     Black,
 }
+impl Color {
+    /// The other side
+    pub const fn opponent(self) -> Self {
+        match self {
+            Self::White => Self::Black,
+            Self::Black => Self::White,
+        }
+    }
+}
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Piece {
     King,
     Queen,
@@ -84,8 +69,21 @@ pub enum Piece {
     Knight,
     Pawn,
 }
+
+/// Every kind of piece, from the king down
+pub const ALL_PIECES: [Piece; 6] = [
+    Piece::King,
+    Piece::Queen,
+    Piece::Rook,
+    Piece::Bishop,
+    Piece::Knight,
+    Piece::Pawn,
+];
+
+/// The pieces a pawn may promote to
+pub const PROMOTION_PIECES: [Piece; 4] = [Piece::Queen, Piece::Rook, Piece::Bishop, Piece::Knight];
 impl Piece {
-    fn to_string(&self) -> &'static str {
+    fn to_string(self) -> &'static str {
         match self {
             Self::King => "♚",
             Self::Queen => "♛",
@@ -95,6 +93,29 @@ impl Piece {
             Self::Pawn => "♟",
         }
     }
+    /// The piece's uppercase letter in algebraic notation
+    pub const fn letter(self) -> char {
+        match self {
+            Self::King => 'K',
+            Self::Queen => 'Q',
+            Self::Rook => 'R',
+            Self::Bishop => 'B',
+            Self::Knight => 'N',
+            Self::Pawn => 'P',
+        }
+    }
+    /// The piece for an uppercase letter in algebraic notation
+    pub const fn from_letter(letter: char) -> Option<Self> {
+        match letter {
+            'K' => Some(Self::King),
+            'Q' => Some(Self::Queen),
+            'R' => Some(Self::Rook),
+            'B' => Some(Self::Bishop),
+            'N' => Some(Self::Knight),
+            'P' => Some(Self::Pawn),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -106,28 +127,66 @@ impl PieceBoard {
     const fn new(piece: Piece, board: BitBoard) -> Self {
         Self { piece, board }
     }
+    /// The kind of piece tracked by this board
+    pub const fn piece(&self) -> Piece {
+        self.piece
+    }
+    /// The squares occupied by this kind of piece
+    pub const fn board(&self) -> BitBoard {
+        self.board
+    }
 }
 impl Occupied for PieceBoard {
     fn occupied(&self, square: &Square) -> bool {
-        self.board.intersects(&BitBoard::from_square(&square))
+        self.board.intersects(&BitBoard::from_square(square))
+    }
+}
+impl PieceBoard {
+    /// Castling moves the king and the rook, leaving everything else alone
+    fn apply_castle(&self, mv: &Move, side: CastleSide) -> Self {
+        match self.piece {
+            Piece::King if self.board.intersects(&mv.origin_board()) => Self::new(
+                self.piece,
+                self.board
+                    .intersection(&mv.origin_board().complement())
+                    .set(&side.king_destination(&mv.from())),
+            ),
+            Piece::Rook if self.board.intersects(&mv.target_board()) => Self::new(
+                self.piece,
+                self.board
+                    .intersection(&mv.target_board().complement())
+                    .set(&side.rook_destination(&mv.from())),
+            ),
+            _ => *self,
+        }
     }
 }
 impl Movable for PieceBoard {
     fn apply_move(&self, mv: &Move) -> Self {
-        if self.board.intersects(&mv.from.board) {
+        if let MoveKind::Castle(side) = mv.kind() {
+            self.apply_castle(mv, side)
+        } else if mv.is_en_passant() && self.occupied(&mv.captured_square()) {
+            Self::new(self.piece, self.board.unset(&mv.captured_square()))
+        } else if self.board.intersects(&mv.origin_board()) && mv.promotion().is_some() {
+            Self::new(
+                self.piece,
+                // the piece is replaced, so only remove it from the from square
+                self.board.intersection(&mv.origin_board().complement()),
+            )
+        } else if self.board.intersects(&mv.origin_board()) {
             Self::new(
                 self.piece,
                 self.board
                     // remove any pice in the from square
-                    .intersection(&mv.from.board.complement())
+                    .intersection(&mv.origin_board().complement())
                     // and place the piece in the to square
-                    .union(&mv.to.board),
+                    .union(&mv.target_board()),
             )
-        } else if self.board.intersects(&mv.to.board) {
+        } else if self.board.intersects(&mv.target_board()) {
             Self::new(
                 self.piece,
                 // Remove any piece in the to square
-                self.board.intersection(&mv.to.board.complement()),
+                self.board.intersection(&mv.target_board().complement()),
             )
         } else {
             *self
@@ -167,7 +226,7 @@ impl<'a> Iterator for PiecesIter<'a> {
 }
 
 /// A representation of a side's pieces
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub struct Pieces {
     king: PieceBoard,
     queens: PieceBoard,
@@ -203,9 +262,52 @@ impl Pieces {
         }
     }
     /// Return all of the boards for all of the pieces
-    pub fn iter_pieces(&self) -> PiecesIter {
+    pub fn iter_pieces(&self) -> PiecesIter<'_> {
         PiecesIter::new(self)
     }
+    /// Return the squares occupied by any piece in the set
+    pub const fn all(&self) -> BitBoard {
+        self.all
+    }
+    /// Return the board for a particular kind of piece
+    pub const fn piece_board(&self, piece: Piece) -> &PieceBoard {
+        match piece {
+            Piece::King => &self.king,
+            Piece::Queen => &self.queens,
+            Piece::Rook => &self.rooks,
+            Piece::Bishop => &self.bishops,
+            Piece::Knight => &self.knights,
+            Piece::Pawn => &self.pawns,
+        }
+    }
+}
+impl Pieces {
+    fn piece_board_mut(&mut self, piece: Piece) -> &mut PieceBoard {
+        match piece {
+            Piece::King => &mut self.king,
+            Piece::Queen => &mut self.queens,
+            Piece::Rook => &mut self.rooks,
+            Piece::Bishop => &mut self.bishops,
+            Piece::Knight => &mut self.knights,
+            Piece::Pawn => &mut self.pawns,
+        }
+    }
+    /// Place a piece on an empty square, or lift it from its square, in place
+    fn toggle(&mut self, piece: Piece, square: &Square) {
+        let square = BitBoard::from_square(square);
+        let piece_board = self.piece_board_mut(piece);
+        piece_board.board = piece_board.board.exclusive_or(&square);
+        self.all = self.all.exclusive_or(&square);
+    }
+    /// The kind of piece on a square, if it's one of this set's
+    fn piece_on(&self, square: &Square) -> Option<Piece> {
+        if !self.all.occupied(square) {
+            return None;
+        }
+        self.iter_pieces()
+            .find(|pieces| pieces.occupied(square))
+            .map(|pieces| pieces.piece)
+    }
 }
 impl Occupied for Pieces {
     /// Return whether the given square is occupied by this piece set.
@@ -216,11 +318,22 @@ impl Occupied for Pieces {
 impl Movable for Pieces {
     /// Apply a move to the pice set and return a new one
     fn apply_move(&self, mv: &Move) -> Self {
+        // Only promote if it's our pawn that's moving
+        let promotion = match mv.promotion() {
+            Some(piece) if self.pawns.board.intersects(&mv.origin_board()) => Some(piece),
+            _ => None,
+        };
+        let promote = |pieces: PieceBoard| match promotion {
+            Some(piece) if piece == pieces.piece => {
+                PieceBoard::new(piece, pieces.board.union(&mv.target_board()))
+            }
+            _ => pieces,
+        };
         let king = self.king.apply_move(mv);
-        let queens = self.queens.apply_move(mv);
-        let rooks = self.rooks.apply_move(mv);
-        let bishops = self.bishops.apply_move(mv);
-        let knights = self.knights.apply_move(mv);
+        let queens = promote(self.queens.apply_move(mv));
+        let rooks = promote(self.rooks.apply_move(mv));
+        let bishops = promote(self.bishops.apply_move(mv));
+        let knights = promote(self.knights.apply_move(mv));
         let pawns = self.pawns.apply_move(mv);
         Self {
             king,
@@ -283,7 +396,7 @@ impl<'a> Iterator for BoardIter<'a> {
 }
 
 /// A representation of the game board
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Board {
     white: Pieces,
     black: Pieces,
@@ -315,9 +428,16 @@ impl Board {
         )
     }
     /// Return all of the bitboards comprising the board
-    pub fn iter_pieces(&self) -> BoardIter {
+    pub fn iter_pieces(&self) -> BoardIter<'_> {
         BoardIter::new(self)
     }
+    /// Return the piece set for the given side
+    pub const fn pieces(&self, color: Color) -> &Pieces {
+        match color {
+            Color::White => &self.white,
+            Color::Black => &self.black,
+        }
+    }
     pub fn occupant(&self, square: &Square) -> Option<(Piece, Color)> {
         let white_iter = self
             .white
@@ -332,6 +452,85 @@ impl Board {
         white_iter.chain(black_iter).next()
     }
 }
+/// In-place move making
+///
+/// Making a move in place only touches the squares the move involves, where
+/// `apply_move` builds every piece board afresh. A search makes and unmakes
+/// moves down a single board instead of copying it for every node.
+impl Board {
+    fn pieces_mut(&mut self, color: Color) -> &mut Pieces {
+        match color {
+            Color::White => &mut self.white,
+            Color::Black => &mut self.black,
+        }
+    }
+    /// Apply a move in place, returning the piece it captured, if any
+    ///
+    /// As with `apply_move`, the move is assumed to be legal. The move and
+    /// its captured piece are all `unmake_move` needs to take it back.
+    ///
+    /// Debug builds panic if there's no piece on the from square. Release
+    /// builds leave the board as it was, and unmaking such a move would
+    /// then corrupt it.
+    pub fn make_move(&mut self, mv: &Move) -> Option<Piece> {
+        let from = mv.from();
+        debug_assert!(self.occupied(&from), "no piece on {} to move", from);
+        let (piece, color) = self.occupant(&from)?;
+        if let MoveKind::Castle(side) = mv.kind() {
+            self.castle(color, mv, side);
+            return None;
+        }
+        let captured_square = mv.captured_square();
+        let captured = self.pieces(color.opponent()).piece_on(&captured_square);
+        if let Some(captured) = captured {
+            self.pieces_mut(color.opponent())
+                .toggle(captured, &captured_square);
+        }
+        let pieces = self.pieces_mut(color);
+        pieces.toggle(piece, &from);
+        pieces.toggle(mv.promotion().unwrap_or(piece), &mv.to());
+        captured
+    }
+    /// Take back a move made with `make_move`, given the piece it captured
+    pub fn unmake_move(&mut self, mv: &Move, captured: Option<Piece>) {
+        if let MoveKind::Castle(side) = mv.kind() {
+            let king = side.king_destination(&mv.from());
+            if let Some((_, color)) = self.occupant(&king) {
+                self.castle(color, mv, side);
+            }
+            return;
+        }
+        let to = mv.to();
+        let (placed, color) = match self.occupant(&to) {
+            Some(occupant) => occupant,
+            None => return,
+        };
+        let pieces = self.pieces_mut(color);
+        pieces.toggle(placed, &to);
+        let moved = if mv.promotion().is_some() {
+            Piece::Pawn
+        } else {
+            placed
+        };
+        pieces.toggle(moved, &mv.from());
+        if let Some(captured) = captured {
+            self.pieces_mut(color.opponent())
+                .toggle(captured, &mv.captured_square());
+        }
+    }
+    /// Move the king and rook between their castling squares, either way
+    ///
+    /// In Chess960 the king or rook may already be on its destination, in
+    /// which case toggling it off and on again leaves it there.
+    fn castle(&mut self, color: Color, mv: &Move, side: CastleSide) {
+        let king = mv.from();
+        let pieces = self.pieces_mut(color);
+        pieces.toggle(Piece::King, &king);
+        pieces.toggle(Piece::King, &side.king_destination(&king));
+        pieces.toggle(Piece::Rook, &mv.to());
+        pieces.toggle(Piece::Rook, &side.rook_destination(&king));
+    }
+}
 impl Occupied for Board {
     /// Check whether a square is occupied
     fn occupied(&self, square: &Square) -> bool {
@@ -381,16 +580,15 @@ impl Board {
                     SquareColor::White => empty_spaces.on_green(),
                     SquareColor::Black => empty_spaces.on_blue(),
                 };
-                let middle_line;
-                if self.occupied(square) {
+                let middle_line = if self.occupied(square) {
                     let occupant = self.occupant(square).unwrap();
-                    middle_line = match occupant.1 {
+                    match occupant.1 {
                         Color::White => format!(" {}  ", occupant.0.to_string().white()),
                         Color::Black => format!(" {}  ", occupant.0.to_string().black()),
-                    };
+                    }
                 } else {
-                    middle_line = empty_spaces.into();
-                }
+                    empty_spaces.into()
+                };
                 match square.color() {
                     SquareColor::White => {
                         [empty_line.to_string(), middle_line.on_green().to_string()]
@@ -407,7 +605,7 @@ impl Board {
                 (0..2).for_each(|idx| {
                     square_blocks
                         .iter()
-                        .for_each(|block| board_str.push_str(&(block.get(idx)).unwrap()));
+                        .for_each(|block| board_str.push_str(block.get(idx).unwrap()));
                     board_str.push('\n');
                 })
             });
@@ -420,6 +618,7 @@ impl Board {
 mod test {
     use super::*;
     use crate::bitboard;
+    use crate::bitboard::NO_SQUARES;
 
     // *****************************************************************
     // Test starting constants
@@ -535,12 +734,14 @@ mod test {
     }
 
     #[test]
+    #[allow(clippy::bool_comparison)]
     fn test_occupied_true() {
         assert!(Board::fresh_game().occupied(&Square::A1) == true);
         assert!(Board::fresh_game().occupied(&Square::A8) == true);
     }
 
     #[test]
+    #[allow(clippy::bool_comparison)]
     fn test_occupied_false() {
         assert!(Board::fresh_game().occupied(&Square::A3) == false);
         assert!(Board::fresh_game().occupied(&Square::A6) == false);
@@ -580,6 +781,105 @@ mod test {
         assert!(!new_board.occupied(&Square::A4));
     }
 
+    /// It moves both the king and the rook
+    #[test]
+    fn test_apply_castle() {
+        let board = Board::new(
+            Pieces::new(
+                STARTING_WHITE_KING,
+                NO_SQUARES,
+                STARTING_WHITE_ROOKS,
+                NO_SQUARES,
+                NO_SQUARES,
+                NO_SQUARES,
+            ),
+            Pieces::new(
+                STARTING_BLACK_KING,
+                NO_SQUARES,
+                STARTING_BLACK_ROOKS,
+                NO_SQUARES,
+                NO_SQUARES,
+                NO_SQUARES,
+            ),
+        );
+        let new_board =
+            board.apply_move(&Move::castle(&Square::E1, &Square::A1, CastleSide::Queen));
+        assert!(new_board.white.king.board == BitBoard::from_square(&Square::C1));
+        assert!(
+            new_board.white.rooks.board == BitBoard::from_squares(&vec![Square::D1, Square::H1])
+        );
+        assert!(new_board.black.king.board == STARTING_BLACK_KING);
+        assert!(new_board.black.rooks.board == STARTING_BLACK_ROOKS);
+        let new_board = board.apply_move(&Move::castle(&Square::E8, &Square::H8, CastleSide::King));
+        assert!(new_board.black.king.board == BitBoard::from_square(&Square::G8));
+        assert!(
+            new_board.black.rooks.board == BitBoard::from_squares(&vec![Square::A8, Square::F8])
+        );
+        assert!(new_board.white.rooks.board == STARTING_WHITE_ROOKS);
+    }
+
+    /// It removes the captured pawn from beside the moving pawn
+    #[test]
+    fn test_apply_en_passant() {
+        let board = Board::new(
+            Pieces::new(
+                STARTING_WHITE_KING,
+                NO_SQUARES,
+                NO_SQUARES,
+                NO_SQUARES,
+                NO_SQUARES,
+                BitBoard::from_square(&Square::E5),
+            ),
+            Pieces::new(
+                STARTING_BLACK_KING,
+                NO_SQUARES,
+                NO_SQUARES,
+                NO_SQUARES,
+                NO_SQUARES,
+                BitBoard::from_square(&Square::D5),
+            ),
+        );
+        let mv = Move::en_passant(&Square::E5, &Square::D6);
+        assert!(mv.captured_square() == Square::D5);
+        let new_board = board.apply_move(&mv);
+        assert!(new_board.white.pawns.board == BitBoard::from_square(&Square::D6));
+        assert!(new_board.black.pawns.board.is_empty());
+    }
+
+    /// It replaces the pawn with the promoted piece, capturing as usual
+    #[test]
+    fn test_apply_promotion() {
+        let board = Board::new(
+            Pieces::new(
+                STARTING_WHITE_KING,
+                NO_SQUARES,
+                NO_SQUARES,
+                NO_SQUARES,
+                NO_SQUARES,
+                BitBoard::from_squares(&vec![Square::B7, Square::C7]),
+            ),
+            Pieces::new(
+                STARTING_BLACK_KING,
+                NO_SQUARES,
+                NO_SQUARES,
+                NO_SQUARES,
+                BitBoard::from_square(&Square::B8),
+                NO_SQUARES,
+            ),
+        );
+        let new_board =
+            board.apply_move(&Move::new(&Square::C7, &Square::B8).with_promotion(Piece::Knight));
+        assert!(new_board.white.pawns.board == BitBoard::from_square(&Square::B7));
+        assert!(new_board.white.knights.board == BitBoard::from_square(&Square::B8));
+        assert!(new_board.white.queens.board.is_empty());
+        assert!(new_board.black.knights.board.is_empty());
+        assert!(new_board.occupant(&Square::B8) == Some((Piece::Knight, Color::White)));
+        let new_board =
+            board.apply_move(&Move::new(&Square::B7, &Square::A8).with_promotion(Piece::Queen));
+        assert!(new_board.white.queens.board == BitBoard::from_square(&Square::A8));
+        assert!(new_board.white.pawns.board == BitBoard::from_square(&Square::C7));
+    }
+
     /// It removes the piece in the target location
     #[test]
     fn test_apply_move_from_empty_to_occupied() {
@@ -591,4 +891,14 @@ mod test {
         assert!(!new_board.occupied(&Square::A2));
         assert!(!new_board.white.pawns.occupied(&Square::A2));
     }
+
+    /// Making a move from an empty square is a bug, not a move which does
+    /// nothing, as unmaking it would move the piece on its target
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "no piece on E4 to move")]
+    fn test_make_move_from_empty_square() {
+        let mut board = Board::fresh_game();
+        board.make_move(&Move::new(&Square::E4, &Square::E2));
+    }
 }
@@ -0,0 +1,247 @@
+//! Castling rights and castling geometry
+
+use crate::board::Color;
+use crate::square::Square;
+
+/// The wing a side castles towards
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CastleSide {
+    King,
+    Queen,
+}
+impl CastleSide {
+    /// The square a castling rook starts on
+    pub const fn rook_square(self, color: Color) -> Square {
+        match (color, self) {
+            (Color::White, Self::King) => Square::H1,
+            (Color::White, Self::Queen) => Square::A1,
+            (Color::Black, Self::King) => Square::H8,
+            (Color::Black, Self::Queen) => Square::A8,
+        }
+    }
+    /// The square the king lands on, on the same rank as the king starts
+    pub const fn king_destination(self, king: &Square) -> Square {
+        match (Self::is_white_rank(king), self) {
+            (true, Self::King) => Square::G1,
+            (true, Self::Queen) => Square::C1,
+            (false, Self::King) => Square::G8,
+            (false, Self::Queen) => Square::C8,
+        }
+    }
+    /// The square the rook lands on, on the same rank as the king starts
+    pub const fn rook_destination(self, king: &Square) -> Square {
+        match (Self::is_white_rank(king), self) {
+            (true, Self::King) => Square::F1,
+            (true, Self::Queen) => Square::D1,
+            (false, Self::King) => Square::F8,
+            (false, Self::Queen) => Square::D8,
+        }
+    }
+    const fn is_white_rank(square: &Square) -> bool {
+        (*square as u8) < 8
+    }
+}
+
+pub const CASTLE_SIDES: [CastleSide; 2] = [CastleSide::King, CastleSide::Queen];
+
+/// Which castling moves each side is still entitled to make
+///
+/// Each right records the square of the rook it castles with, so that
+/// Chess960 positions, where the rooks and king may start on other files,
+/// are handled the same way as standard ones. The king's starting square is
+/// kept too, so that moving it can revoke its side's rights.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CastlingRights {
+    kings: [Square; 2],
+    rooks: [Option<Square>; 4],
+}
+impl CastlingRights {
+    /// No castling rights for either side
+    pub const fn none() -> Self {
+        Self {
+            kings: [Square::E1, Square::E8],
+            rooks: [None; 4],
+        }
+    }
+    /// Full castling rights for both sides, from the standard squares
+    pub const fn all() -> Self {
+        Self::none()
+            .grant(Color::White, CastleSide::King)
+            .grant(Color::White, CastleSide::Queen)
+            .grant(Color::Black, CastleSide::King)
+            .grant(Color::Black, CastleSide::Queen)
+    }
+    const fn index(color: Color, side: CastleSide) -> usize {
+        match (color, side) {
+            (Color::White, CastleSide::King) => 0,
+            (Color::White, CastleSide::Queen) => 1,
+            (Color::Black, CastleSide::King) => 2,
+            (Color::Black, CastleSide::Queen) => 3,
+        }
+    }
+    const fn king_index(color: Color) -> usize {
+        match color {
+            Color::White => 0,
+            Color::Black => 1,
+        }
+    }
+    /// Whether the given side may still castle on the given wing
+    pub const fn has(&self, color: Color, side: CastleSide) -> bool {
+        self.rooks[Self::index(color, side)].is_some()
+    }
+    /// The starting square of the rook the given side may castle with on the
+    /// given wing, if it still may
+    pub const fn rook(&self, color: Color, side: CastleSide) -> Option<Square> {
+        self.rooks[Self::index(color, side)]
+    }
+    /// The square the given side's king castles from
+    pub const fn king(&self, color: Color) -> Square {
+        self.kings[Self::king_index(color)]
+    }
+    /// Whether neither side may castle at all
+    pub const fn is_empty(&self) -> bool {
+        let mut idx = 0;
+        while idx < 4 {
+            if self.rooks[idx].is_some() {
+                return false;
+            }
+            idx += 1;
+        }
+        true
+    }
+    /// Grant a castling right with the rook on its standard square
+    pub const fn grant(&self, color: Color, side: CastleSide) -> Self {
+        self.grant_rook(color, side, &side.rook_square(color))
+    }
+    /// Grant a castling right with the rook on the given square
+    pub const fn grant_rook(&self, color: Color, side: CastleSide, rook: &Square) -> Self {
+        let mut rooks = self.rooks;
+        rooks[Self::index(color, side)] = Some(*rook);
+        Self {
+            kings: self.kings,
+            rooks,
+        }
+    }
+    /// Set the square the given side's king castles from
+    pub const fn with_king(&self, color: Color, king: &Square) -> Self {
+        let mut kings = self.kings;
+        kings[Self::king_index(color)] = *king;
+        Self {
+            kings,
+            rooks: self.rooks,
+        }
+    }
+    /// Revoke a castling right
+    pub const fn revoke(&self, color: Color, side: CastleSide) -> Self {
+        let mut rooks = self.rooks;
+        rooks[Self::index(color, side)] = None;
+        Self {
+            kings: self.kings,
+            rooks,
+        }
+    }
+    /// Revoke both of a side's castling rights
+    pub const fn revoke_all(&self, color: Color) -> Self {
+        self.revoke(color, CastleSide::King)
+            .revoke(color, CastleSide::Queen)
+    }
+    /// Revoke any rights lost by a move between two squares
+    ///
+    /// Moving the king loses both rights for its side, while moving a rook
+    /// from (or capturing a rook on) its starting square loses the right for
+    /// that wing.
+    pub const fn update_for_move(&self, from: &Square, to: &Square) -> Self {
+        self.update_for_square(from).update_for_square(to)
+    }
+    const fn update_for_square(&self, square: &Square) -> Self {
+        let mut rights = *self;
+        let mut idx = 0;
+        while idx < 4 {
+            let (color, side) = match idx {
+                0 => (Color::White, CastleSide::King),
+                1 => (Color::White, CastleSide::Queen),
+                2 => (Color::Black, CastleSide::King),
+                _ => (Color::Black, CastleSide::Queen),
+            };
+            let king = self.king(color) as u8 == *square as u8;
+            let rook = match self.rook(color, side) {
+                Some(rook) => rook as u8 == *square as u8,
+                None => false,
+            };
+            if king || rook {
+                rights = rights.revoke(color, side);
+            }
+            idx += 1;
+        }
+        rights
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_all_and_none() {
+        for color in &[Color::White, Color::Black] {
+            for side in &CASTLE_SIDES {
+                assert!(CastlingRights::all().has(*color, *side));
+                assert!(!CastlingRights::none().has(*color, *side));
+            }
+        }
+    }
+
+    #[test]
+    fn test_grant_and_revoke() {
+        let rights = CastlingRights::none().grant(Color::Black, CastleSide::Queen);
+        assert!(rights.has(Color::Black, CastleSide::Queen));
+        assert!(!rights.has(Color::Black, CastleSide::King));
+        assert!(rights.revoke(Color::Black, CastleSide::Queen).is_empty());
+    }
+
+    #[test]
+    fn test_king_move_revokes_both() {
+        let rights = CastlingRights::all().update_for_move(&Square::E1, &Square::E2);
+        assert!(!rights.has(Color::White, CastleSide::King));
+        assert!(!rights.has(Color::White, CastleSide::Queen));
+        assert!(rights.has(Color::Black, CastleSide::King));
+        assert!(rights.has(Color::Black, CastleSide::Queen));
+    }
+
+    #[test]
+    fn test_rook_move_revokes_one() {
+        let rights = CastlingRights::all().update_for_move(&Square::A8, &Square::A5);
+        assert!(!rights.has(Color::Black, CastleSide::Queen));
+        assert!(rights.has(Color::Black, CastleSide::King));
+    }
+
+    #[test]
+    fn test_rook_capture_revokes_one() {
+        let rights = CastlingRights::all().update_for_move(&Square::B7, &Square::H1);
+        assert!(!rights.has(Color::White, CastleSide::King));
+        assert!(rights.has(Color::White, CastleSide::Queen));
+    }
+
+    #[test]
+    fn test_chess960_rooks() {
+        let rights = CastlingRights::none()
+            .with_king(Color::White, &Square::B1)
+            .grant_rook(Color::White, CastleSide::King, &Square::F1)
+            .grant_rook(Color::White, CastleSide::Queen, &Square::A1);
+        assert!(rights.king(Color::White) == Square::B1);
+        assert!(rights.rook(Color::White, CastleSide::King) == Some(Square::F1));
+        assert!(rights.rook(Color::Black, CastleSide::King).is_none());
+        // The standard squares mean nothing here
+        assert!(rights.update_for_move(&Square::H1, &Square::E1) == rights);
+        let rook_moved = rights.update_for_move(&Square::F1, &Square::F5);
+        assert!(!rook_moved.has(Color::White, CastleSide::King));
+        assert!(rook_moved.has(Color::White, CastleSide::Queen));
+        assert!(rights.update_for_move(&Square::B1, &Square::C2).is_empty());
+    }
+
+    #[test]
+    fn test_destinations() {
+        assert!(CastleSide::King.king_destination(&Square::E1) == Square::G1);
+        assert!(CastleSide::Queen.rook_destination(&Square::E8) == Square::D8);
+    }
+}
@@ -0,0 +1,432 @@
+//! Extended Position Description
+//!
+//! An EPD record is the first four fields of a FEN string followed by any
+//! number of operations, each an opcode and its operands ended by a
+//! semicolon. Test suites use it to give a best move and a name for each
+//! position:
+//!
+//! ```text
+//! 2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id "WAC.001";
+//! ```
+//!
+//! Operands are kept as the strings they were written as. Accessors are
+//! provided for the common opcodes, parsing numeric operands as they're read.
+
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::fen::FenError;
+use crate::game_state::GameState;
+
+/// An opcode and its operands
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Operation {
+    opcode: String,
+    operands: Vec<String>,
+}
+impl Operation {
+    pub fn new(opcode: &str, operands: Vec<String>) -> Self {
+        Self {
+            opcode: opcode.to_string(),
+            operands,
+        }
+    }
+    pub fn opcode(&self) -> &str {
+        &self.opcode
+    }
+    pub fn operands(&self) -> &[String] {
+        &self.operands
+    }
+    /// Whether the opcode's operands are strings, which are always quoted
+    fn takes_string(&self) -> bool {
+        let comment = self.opcode.len() == 2
+            && self.opcode.starts_with('c')
+            && self.opcode.ends_with(|c: char| c.is_ascii_digit());
+        self.opcode == "id" || comment
+    }
+}
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.opcode)?;
+        for operand in self.operands.iter() {
+            let quote = self.takes_string()
+                || operand.is_empty()
+                || operand.contains(|c: char| c.is_whitespace() || c == ';');
+            if quote {
+                write!(f, " \"{}\"", operand)?;
+            } else {
+                write!(f, " {}", operand)?;
+            }
+        }
+        write!(f, ";")
+    }
+}
+
+/// What was wrong with an EPD record
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EpdError {
+    /// The position fields were invalid
+    Fen(FenError),
+    /// The halfmove clock given by `hmvc` wasn't a non-negative integer
+    InvalidHalfmoveClock(String),
+    /// The fullmove number given by `fmvn` wasn't a positive integer
+    InvalidFullmoveNumber(String),
+    /// An opcode didn't start with a letter or contained anything but
+    /// letters, digits and underscores
+    InvalidOpcode(String),
+    /// A quoted operand was never closed
+    UnterminatedString(String),
+    /// The record ended before the operation's semicolon
+    MissingSemicolon(String),
+}
+impl fmt::Display for EpdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fen(error) => write!(f, "invalid position: {}", error),
+            Self::InvalidHalfmoveClock(operand) => {
+                write!(f, "invalid halfmove clock {:?} in hmvc", operand)
+            }
+            Self::InvalidFullmoveNumber(operand) => {
+                write!(f, "invalid fullmove number {:?} in fmvn", operand)
+            }
+            Self::InvalidOpcode(opcode) => write!(f, "invalid opcode {:?}", opcode),
+            Self::UnterminatedString(opcode) => {
+                write!(f, "unterminated string in operation {:?}", opcode)
+            }
+            Self::MissingSemicolon(opcode) => {
+                write!(f, "missing semicolon after operation {:?}", opcode)
+            }
+        }
+    }
+}
+impl Error for EpdError {}
+impl From<FenError> for EpdError {
+    fn from(error: FenError) -> Self {
+        Self::Fen(error)
+    }
+}
+
+/// A position and the operations describing it
+pub struct Epd {
+    state: GameState,
+    operations: Vec<Operation>,
+}
+impl Epd {
+    /// Construct a record for a position with no operations
+    pub fn new(state: GameState) -> Self {
+        Self {
+            state,
+            operations: Vec::new(),
+        }
+    }
+    /// Parse a single EPD record
+    ///
+    /// The halfmove clock and fullmove number are taken from the `hmvc` and
+    /// `fmvn` operations if they're present.
+    pub fn parse(record: &str) -> Result<Self, EpdError> {
+        let mut rest = record.trim();
+        let mut fields = Vec::with_capacity(4);
+        while fields.len() < 4 && !rest.is_empty() {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            fields.push(&rest[..end]);
+            rest = rest[end..].trim_start();
+        }
+        if fields.len() < 4 {
+            return Err(FenError::FieldCount(fields.len()).into());
+        }
+        let operations = parse_operations(rest)?;
+        let state = GameState::from_fen(&fields.join(" "))?;
+        // A clock operation without an operand is as wrong as a bad one
+        let operand = |opcode| {
+            operations
+                .iter()
+                .find(|operation: &&Operation| operation.opcode == opcode)
+                .map(|operation| {
+                    operation
+                        .operands
+                        .first()
+                        .map_or("", |operand| operand.as_str())
+                })
+        };
+        let halfmove_clock = match operand("hmvc") {
+            Some(operand) => operand
+                .parse::<u32>()
+                .map_err(|_| EpdError::InvalidHalfmoveClock(operand.to_string()))?,
+            None => 0,
+        };
+        let fullmove_number = match operand("fmvn") {
+            Some(operand) => operand
+                .parse::<u32>()
+                .ok()
+                .filter(|number| *number > 0)
+                .ok_or_else(|| EpdError::InvalidFullmoveNumber(operand.to_string()))?,
+            None => 1,
+        };
+        let meta = state.meta().with_clocks(halfmove_clock, fullmove_number);
+        Ok(Self {
+            state: GameState::new(state.board().clone(), state.turn(), meta),
+            operations,
+        })
+    }
+    /// Parse every record in a test suite, one per line, skipping blank
+    /// lines
+    pub fn parse_all(text: &str) -> impl Iterator<Item = Result<Self, EpdError>> + '_ {
+        text.lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(Self::parse)
+    }
+    pub fn state(&self) -> &GameState {
+        &self.state
+    }
+    /// All operations, in the order they were given
+    pub fn operations(&self) -> &[Operation] {
+        &self.operations
+    }
+    /// The operands of the first operation with the given opcode
+    pub fn operands(&self, opcode: &str) -> Option<&[String]> {
+        self.operations
+            .iter()
+            .find(|operation| operation.opcode == opcode)
+            .map(|operation| operation.operands())
+    }
+    /// Set the operands for an opcode, replacing any existing operation
+    pub fn set_operation(&mut self, opcode: &str, operands: Vec<String>) {
+        match self
+            .operations
+            .iter_mut()
+            .find(|operation| operation.opcode == opcode)
+        {
+            Some(operation) => operation.operands = operands,
+            None => self.operations.push(Operation::new(opcode, operands)),
+        }
+    }
+    /// Remove every operation with the given opcode
+    pub fn remove_operation(&mut self, opcode: &str) {
+        self.operations
+            .retain(|operation| operation.opcode != opcode);
+    }
+    fn first_operand(&self, opcode: &str) -> Option<&str> {
+        self.operands(opcode)
+            .and_then(|operands| operands.first())
+            .map(|operand| operand.as_str())
+    }
+}
+/// Common opcodes
+impl Epd {
+    /// Best moves (`bm`), in SAN
+    pub fn best_moves(&self) -> Option<&[String]> {
+        self.operands("bm")
+    }
+    /// Moves to avoid (`am`), in SAN
+    pub fn avoid_moves(&self) -> Option<&[String]> {
+        self.operands("am")
+    }
+    /// The position's name in its suite (`id`)
+    pub fn id(&self) -> Option<&str> {
+        self.first_operand("id")
+    }
+    /// One of the ten comments `c0` to `c9`
+    pub fn comment(&self, number: u8) -> Option<&str> {
+        self.first_operand(&format!("c{}", number))
+    }
+    /// The depth searched to produce the analysis (`acd`)
+    pub fn analysis_depth(&self) -> Option<u32> {
+        self.first_operand("acd")
+            .and_then(|operand| operand.parse().ok())
+    }
+    /// The evaluation in centipawns from the side to move's point of view
+    /// (`ce`)
+    pub fn centipawn_evaluation(&self) -> Option<i32> {
+        self.first_operand("ce")
+            .and_then(|operand| operand.parse().ok())
+    }
+    /// The predicted variation (`pv`), in SAN
+    pub fn predicted_variation(&self) -> Option<&[String]> {
+        self.operands("pv")
+    }
+}
+impl FromStr for Epd {
+    type Err = EpdError;
+    fn from_str(record: &str) -> Result<Self, Self::Err> {
+        Self::parse(record)
+    }
+}
+impl fmt::Display for Epd {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let fen = self.state.to_fen();
+        let fields = fen.split(' ').take(4).collect::<Vec<&str>>();
+        write!(f, "{}", fields.join(" "))?;
+        for operation in self.operations.iter() {
+            write!(f, " {}", operation)?;
+        }
+        Ok(())
+    }
+}
+
+/// Split the text after the position into operations
+fn parse_operations(text: &str) -> Result<Vec<Operation>, EpdError> {
+    let mut operations = Vec::new();
+    let mut chars = text.chars().peekable();
+    loop {
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+        if chars.peek().is_none() {
+            return Ok(operations);
+        }
+        let mut opcode = String::new();
+        while let Some(c) = chars.peek().filter(|c| !c.is_whitespace() && **c != ';') {
+            opcode.push(*c);
+            chars.next();
+        }
+        let valid = opcode.starts_with(|c: char| c.is_ascii_alphabetic())
+            && opcode
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid {
+            return Err(EpdError::InvalidOpcode(opcode));
+        }
+        let mut operands = Vec::new();
+        loop {
+            match chars.next() {
+                Some(';') => break,
+                Some(c) if c.is_whitespace() => continue,
+                Some('"') => {
+                    let mut operand = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('"') => break,
+                            Some(c) => operand.push(c),
+                            None => return Err(EpdError::UnterminatedString(opcode)),
+                        }
+                    }
+                    operands.push(operand);
+                }
+                Some(c) => {
+                    let mut operand = c.to_string();
+                    while let Some(c) = chars.peek().filter(|c| !c.is_whitespace() && **c != ';') {
+                        operand.push(*c);
+                        chars.next();
+                    }
+                    operands.push(operand);
+                }
+                None => return Err(EpdError::MissingSemicolon(opcode)),
+            }
+        }
+        operations.push(Operation { opcode, operands });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::board::Color;
+
+    const WAC_1: &str =
+        "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id \"WAC.001\";";
+
+    #[test]
+    fn test_parse() {
+        let epd = Epd::parse(WAC_1).unwrap();
+        assert!(epd.state().turn() == Color::White);
+        assert!(epd.best_moves() == Some(&["Qg6".to_string()][..]));
+        assert!(epd.avoid_moves().is_none());
+        assert!(epd.id() == Some("WAC.001"));
+        assert!(epd.operations().len() == 2);
+        assert!(epd.to_string() == WAC_1);
+    }
+
+    #[test]
+    fn test_analysis_opcodes() {
+        let epd = Epd::parse(
+            "6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - acd 12; ce -350; pv Ra8#; c0 \"back rank; mate\"; c1 \"\";",
+        )
+        .unwrap();
+        assert!(epd.analysis_depth() == Some(12));
+        assert!(epd.centipawn_evaluation() == Some(-350));
+        assert!(epd.predicted_variation().unwrap() == ["Ra8#"]);
+        assert!(epd.comment(0) == Some("back rank; mate"));
+        assert!(epd.comment(1) == Some(""));
+        assert!(epd.comment(2).is_none());
+    }
+
+    #[test]
+    fn test_clocks() {
+        let epd = Epd::parse("4k3/8/8/8/8/8/8/4K3 b - - hmvc 7; fmvn 31;").unwrap();
+        assert!(epd.state().meta().halfmove_clock() == 7);
+        assert!(epd.state().meta().fullmove_number() == 31);
+        let epd = Epd::parse("4k3/8/8/8/8/8/8/4K3 b - -").unwrap();
+        assert!(epd.state().meta().halfmove_clock() == 0);
+        assert!(epd.operations().is_empty());
+    }
+
+    #[test]
+    fn test_write() {
+        let mut epd = Epd::new(GameState::fresh_game());
+        epd.set_operation("bm", vec!["e4".to_string(), "d4".to_string()]);
+        epd.set_operation("id", vec!["start".to_string()]);
+        epd.set_operation("c0", vec!["most popular".to_string()]);
+        assert!(
+            epd.to_string()
+                == "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - bm e4 d4; id \"start\"; c0 \"most popular\";"
+        );
+        epd.set_operation("bm", vec!["Nf3".to_string()]);
+        epd.remove_operation("c0");
+        assert!(
+            epd.to_string()
+                == "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - bm Nf3; id \"start\";"
+        );
+    }
+
+    #[test]
+    fn test_parse_all() {
+        let suite = format!(
+            "{}\n\n{}\n",
+            WAC_1, "r1b1k2r/ppppnppp/2n2q2/2b5/3NP3/2P1B3/PP3PPP/RN1QKB1R w KQkq - bm Nb5; id \"WAC.004\";"
+        );
+        let records = Epd::parse_all(&suite)
+            .collect::<Result<Vec<Epd>, EpdError>>()
+            .unwrap();
+        assert!(records.len() == 2);
+        assert!(records[1].id() == Some("WAC.004"));
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |record| Epd::parse(record).err().unwrap();
+        assert!(error("4k3/8/8/8/8/8/8/4K3 w") == EpdError::Fen(FenError::FieldCount(2)));
+        assert!(
+            error("4k3/8/8/8/8/8/8/4K3 x - -")
+                == EpdError::Fen(FenError::InvalidSideToMove("x".to_string()))
+        );
+        assert!(
+            error("4k3/8/8/8/8/8/8/4K3 w - - bm Kd1")
+                == EpdError::MissingSemicolon("bm".to_string())
+        );
+        assert!(
+            error("4k3/8/8/8/8/8/8/4K3 w - - id \"open;")
+                == EpdError::UnterminatedString("id".to_string())
+        );
+        assert!(
+            error("4k3/8/8/8/8/8/8/4K3 w - - 1bm Kd1;")
+                == EpdError::InvalidOpcode("1bm".to_string())
+        );
+        assert!(
+            error("4k3/8/8/8/8/8/8/4K3 w - - hmvc x;")
+                == EpdError::InvalidHalfmoveClock("x".to_string())
+        );
+        assert!(
+            error("4k3/8/8/8/8/8/8/4K3 w - - hmvc \"1 2\";")
+                == EpdError::InvalidHalfmoveClock("1 2".to_string())
+        );
+        assert!(
+            error("4k3/8/8/8/8/8/8/4K3 w - - hmvc;")
+                == EpdError::InvalidHalfmoveClock("".to_string())
+        );
+        assert!(
+            error("4k3/8/8/8/8/8/8/4K3 w - - fmvn 0;")
+                == EpdError::InvalidFullmoveNumber("0".to_string())
+        );
+    }
+}
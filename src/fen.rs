@@ -0,0 +1,713 @@
+//! Forsyth-Edwards Notation
+//!
+//! A FEN string describes a position in six space-separated fields: piece
+//! placement from rank 8 down, the side to move, castling rights, the en
+//! passant target, the halfmove clock, and the fullmove number.
+//!
+//! ```text
+//! rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1
+//! ```
+//!
+//! The two clocks are often left off, so they're optional when parsing and
+//! default to 0 and 1.
+//!
+//! For Chess960, castling rights may also be given by the rook's file, as
+//! in Shredder-FEN (`HAha`) and X-FEN (`KQkq`, or a file letter when the
+//! king has two rooks on the same wing).
+
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::bitboard::{BitBoard, NO_SQUARES};
+use crate::board::{Board, Color, Piece, Pieces};
+use crate::castling::{CastleSide, CastlingRights, CASTLE_SIDES};
+use crate::game_state::{GameState, StateMeta};
+use crate::square::{File, Rank, Square, SQUARES};
+use crate::traits::Occupied;
+
+/// The FEN of the standard starting position
+pub const STARTING_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+/// How many of each piece a side starts with, in the order `Pieces::new`
+/// takes them
+const STARTING_COUNTS: [u32; 6] = [1, 1, 2, 2, 2, 8];
+
+/// What was wrong with a FEN string
+///
+/// Ranks are numbered as on the board, so the first one in the placement
+/// field is rank 8.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FenError {
+    /// There should be four or six fields
+    FieldCount(usize),
+    /// There should be eight ranks separated by `/`
+    RankCount(usize),
+    /// A rank described more or fewer than eight squares
+    RankLength { rank: u8, squares: usize },
+    /// A character in a rank was neither a piece nor a digit from 1 to 8
+    InvalidPiece { rank: u8, character: char },
+    /// Two digits in a rank were next to each other, like `44`
+    ConsecutiveDigits { rank: u8 },
+    /// A pawn was on the first or eighth rank
+    PawnOnBackRank(Square),
+    /// Each side needs exactly one king
+    KingCount { color: Color, count: u32 },
+    /// A side had more than eight pawns
+    TooManyPawns { color: Color, count: u32 },
+    /// A side had more than sixteen pieces, counting its king and pawns
+    TooManyPieces { color: Color, count: u32 },
+    /// A side had more pieces beyond its starting set than it has pawns
+    /// missing to have promoted them from
+    TooManyPromotions { color: Color, count: u32 },
+    /// The side which just moved was left in check
+    OpponentInCheck(Color),
+    /// The side to move wasn't `w` or `b`
+    InvalidSideToMove(String),
+    /// Castling wasn't `-` or a set of rights without repeats, each naming
+    /// a rook and king which are on their back rank
+    InvalidCastling(String),
+    /// The en passant target wasn't `-` or a square on the right rank
+    InvalidEnPassant(String),
+    /// The halfmove clock wasn't a non-negative integer
+    InvalidHalfmoveClock(String),
+    /// The fullmove number wasn't a positive integer
+    InvalidFullmoveNumber(String),
+}
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::FieldCount(count) => write!(f, "expected 4 or 6 fields, found {}", count),
+            Self::RankCount(count) => write!(f, "expected 8 ranks, found {}", count),
+            Self::RankLength { rank, squares } => {
+                write!(f, "rank {} has {} squares, expected 8", rank, squares)
+            }
+            Self::InvalidPiece { rank, character } => {
+                write!(f, "invalid piece {:?} on rank {}", character, rank)
+            }
+            Self::ConsecutiveDigits { rank } => {
+                write!(f, "consecutive digits on rank {}", rank)
+            }
+            Self::PawnOnBackRank(square) => write!(f, "pawn on back rank at {}", square),
+            Self::KingCount { color, count } => {
+                write!(f, "{:?} has {} kings, expected 1", color, count)
+            }
+            Self::TooManyPawns { color, count } => {
+                write!(f, "{:?} has {} pawns, expected at most 8", color, count)
+            }
+            Self::TooManyPieces { color, count } => {
+                write!(f, "{:?} has {} pieces, expected at most 16", color, count)
+            }
+            Self::TooManyPromotions { color, count } => write!(
+                f,
+                "{:?} has {} promoted pieces, more than its missing pawns allow",
+                color, count
+            ),
+            Self::OpponentInCheck(color) => {
+                write!(f, "{:?} is in check but isn't to move", color)
+            }
+            Self::InvalidSideToMove(field) => write!(f, "invalid side to move {:?}", field),
+            Self::InvalidCastling(field) => write!(f, "invalid castling rights {:?}", field),
+            Self::InvalidEnPassant(field) => write!(f, "invalid en passant target {:?}", field),
+            Self::InvalidHalfmoveClock(field) => write!(f, "invalid halfmove clock {:?}", field),
+            Self::InvalidFullmoveNumber(field) => {
+                write!(f, "invalid fullmove number {:?}", field)
+            }
+        }
+    }
+}
+impl Error for FenError {}
+
+impl Board {
+    /// Parse the piece placement field of a FEN string
+    pub fn from_fen(placement: &str) -> Result<Self, FenError> {
+        let ranks = placement.split('/').collect::<Vec<&str>>();
+        if ranks.len() != 8 {
+            return Err(FenError::RankCount(ranks.len()));
+        }
+        // Indexed by color, then in the order `Pieces::new` takes them
+        let mut boards = [[NO_SQUARES; 6]; 2];
+        for (row, text) in ranks.iter().enumerate() {
+            let rank = 8 - row as u8;
+            let mut file = 0;
+            let mut after_digit = false;
+            for character in text.chars() {
+                if let Some(skip) = character.to_digit(10).filter(|n| (1..=8).contains(n)) {
+                    if after_digit {
+                        return Err(FenError::ConsecutiveDigits { rank });
+                    }
+                    after_digit = true;
+                    file += skip as usize;
+                    continue;
+                }
+                after_digit = false;
+                let piece = Piece::from_letter(character.to_ascii_uppercase())
+                    .ok_or(FenError::InvalidPiece { rank, character })?;
+                if file < 8 {
+                    let square = SQUARES[(rank as usize - 1) * 8 + file];
+                    if piece == Piece::Pawn && (rank == 1 || rank == 8) {
+                        return Err(FenError::PawnOnBackRank(square));
+                    }
+                    let color = if character.is_ascii_uppercase() { 0 } else { 1 };
+                    let board = &mut boards[color][piece_index(piece)];
+                    *board = board.set(&square);
+                }
+                file += 1;
+            }
+            if file != 8 {
+                return Err(FenError::RankLength {
+                    rank,
+                    squares: file,
+                });
+            }
+        }
+        for (color, boards) in [Color::White, Color::Black].iter().zip(boards.iter()) {
+            let count = boards[0].count();
+            if count != 1 {
+                return Err(FenError::KingCount {
+                    color: *color,
+                    count,
+                });
+            }
+            let pawns = boards[5].count();
+            if pawns > 8 {
+                return Err(FenError::TooManyPawns {
+                    color: *color,
+                    count: pawns,
+                });
+            }
+            let count = boards.iter().map(BitBoard::count).sum::<u32>();
+            if count > 16 {
+                return Err(FenError::TooManyPieces {
+                    color: *color,
+                    count,
+                });
+            }
+            // Anything beyond the starting set must have been a pawn
+            let promoted = boards
+                .iter()
+                .zip(STARTING_COUNTS.iter())
+                .map(|(board, start)| board.count().saturating_sub(*start))
+                .sum::<u32>();
+            if promoted > 8 - pawns {
+                return Err(FenError::TooManyPromotions {
+                    color: *color,
+                    count: promoted,
+                });
+            }
+        }
+        let pieces = |boards: &[BitBoard; 6]| {
+            Pieces::new(
+                boards[0], boards[1], boards[2], boards[3], boards[4], boards[5],
+            )
+        };
+        Ok(Board::new(pieces(&boards[0]), pieces(&boards[1])))
+    }
+    /// The piece placement field of a FEN string for this board
+    pub fn to_fen(&self) -> String {
+        let mut fen = String::new();
+        for rank in (0..8).rev() {
+            let mut empty = 0;
+            for square in SQUARES[rank * 8..rank * 8 + 8].iter() {
+                match self.occupant(square) {
+                    Some((piece, color)) => {
+                        if empty > 0 {
+                            fen.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        fen.push(match color {
+                            Color::White => piece.letter(),
+                            Color::Black => piece.letter().to_ascii_lowercase(),
+                        });
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                fen.push_str(&empty.to_string());
+            }
+            if rank > 0 {
+                fen.push('/');
+            }
+        }
+        fen
+    }
+}
+
+impl GameState {
+    /// Parse a position from a FEN string
+    pub fn from_fen(fen: &str) -> Result<Self, FenError> {
+        let fields = fen.split_whitespace().collect::<Vec<&str>>();
+        if fields.len() != 4 && fields.len() != 6 {
+            return Err(FenError::FieldCount(fields.len()));
+        }
+        let board = Board::from_fen(fields[0])?;
+        let turn = parse_turn(fields[1])?;
+        if board.in_check(turn.opponent()) {
+            return Err(FenError::OpponentInCheck(turn.opponent()));
+        }
+        let castling = parse_castling(fields[2], &board)?;
+        let en_passant = parse_en_passant(fields[3], turn)?;
+        let mut meta = StateMeta::new(castling, en_passant);
+        if fields.len() == 6 {
+            let halfmove_clock = fields[4]
+                .parse::<u32>()
+                .map_err(|_| FenError::InvalidHalfmoveClock(fields[4].to_string()))?;
+            let fullmove_number = fields[5]
+                .parse::<u32>()
+                .ok()
+                .filter(|number| *number > 0)
+                .ok_or_else(|| FenError::InvalidFullmoveNumber(fields[5].to_string()))?;
+            meta = meta.with_clocks(halfmove_clock, fullmove_number);
+        }
+        Ok(Self::new(board, turn, meta))
+    }
+    /// The FEN string for this position
+    ///
+    /// Castling rights are written in X-FEN form, which only differs from
+    /// standard FEN for some Chess960 positions.
+    pub fn to_fen(&self) -> String {
+        self.fen_with_castling(false)
+    }
+    /// The FEN string for this position, with castling rights written in
+    /// Shredder-FEN form
+    pub fn to_shredder_fen(&self) -> String {
+        self.fen_with_castling(true)
+    }
+    fn fen_with_castling(&self, shredder: bool) -> String {
+        let meta = self.meta();
+        format!(
+            "{} {} {} {} {} {}",
+            self.board().to_fen(),
+            match self.turn() {
+                Color::White => "w",
+                Color::Black => "b",
+            },
+            castling_to_fen(&meta.castling(), self.board(), shredder),
+            meta.en_passant()
+                .map(|square| square.to_string().to_lowercase())
+                .unwrap_or_else(|| "-".to_string()),
+            meta.halfmove_clock(),
+            meta.fullmove_number(),
+        )
+    }
+}
+impl FromStr for GameState {
+    type Err = FenError;
+    fn from_str(fen: &str) -> Result<Self, Self::Err> {
+        Self::from_fen(fen)
+    }
+}
+
+const fn piece_index(piece: Piece) -> usize {
+    match piece {
+        Piece::King => 0,
+        Piece::Queen => 1,
+        Piece::Rook => 2,
+        Piece::Bishop => 3,
+        Piece::Knight => 4,
+        Piece::Pawn => 5,
+    }
+}
+
+fn parse_turn(field: &str) -> Result<Color, FenError> {
+    match field {
+        "w" => Ok(Color::White),
+        "b" => Ok(Color::Black),
+        _ => Err(FenError::InvalidSideToMove(field.to_string())),
+    }
+}
+
+/// The letter for a castling right, as in `KQkq`
+const fn castling_letter(color: Color, side: CastleSide) -> char {
+    match (color, side) {
+        (Color::White, CastleSide::King) => 'K',
+        (Color::White, CastleSide::Queen) => 'Q',
+        (Color::Black, CastleSide::King) => 'k',
+        (Color::Black, CastleSide::Queen) => 'q',
+    }
+}
+
+/// The letter for a castling right by its rook's file, as in `HAha`
+fn file_letter(color: Color, rook: &Square) -> char {
+    let letter = rook.file().to_char();
+    match color {
+        Color::White => letter.to_ascii_uppercase(),
+        Color::Black => letter,
+    }
+}
+
+/// The squares on a side's back rank holding the given piece, from the A
+/// file to the H file
+fn back_rank(board: &Board, color: Color, piece: Piece) -> Vec<Square> {
+    let rank = match color {
+        Color::White => 0,
+        Color::Black => 7,
+    };
+    let pieces = board.pieces(color).piece_board(piece);
+    SQUARES[rank * 8..rank * 8 + 8]
+        .iter()
+        .filter(|square| pieces.occupied(square))
+        .copied()
+        .collect()
+}
+
+/// The rook furthest from the king on the given wing, which is the one `K`
+/// or `Q` refers to in X-FEN
+fn outermost_rook(board: &Board, color: Color, king: &Square, side: CastleSide) -> Option<Square> {
+    let rooks = back_rank(board, color, Piece::Rook);
+    match side {
+        CastleSide::King => rooks
+            .into_iter()
+            .rev()
+            .find(|rook| *rook as u8 > *king as u8),
+        CastleSide::Queen => rooks.into_iter().find(|rook| (*rook as u8) < *king as u8),
+    }
+}
+
+/// Parse castling rights in standard, X-FEN, or Shredder-FEN form
+///
+/// `K` and `Q` refer to the outermost rook on either side of the king,
+/// while a file letter refers to the rook on that file, which X-FEN uses to
+/// pick out an inner rook and Shredder-FEN uses for every rook. Either way,
+/// the rook and king must be on their back rank.
+fn parse_castling(field: &str, board: &Board) -> Result<CastlingRights, FenError> {
+    let invalid = || FenError::InvalidCastling(field.to_string());
+    if field == "-" {
+        return Ok(CastlingRights::none());
+    }
+    field
+        .chars()
+        .try_fold(CastlingRights::none(), |rights, letter| {
+            let color = if letter.is_ascii_uppercase() {
+                Color::White
+            } else {
+                Color::Black
+            };
+            let king = *back_rank(board, color, Piece::King)
+                .first()
+                .ok_or_else(invalid)?;
+            let (side, rook) = match letter.to_ascii_lowercase() {
+                'k' => (
+                    CastleSide::King,
+                    outermost_rook(board, color, &king, CastleSide::King),
+                ),
+                'q' => (
+                    CastleSide::Queen,
+                    outermost_rook(board, color, &king, CastleSide::Queen),
+                ),
+                letter @ 'a'..='h' => {
+                    let file = File::from_char(letter).ok_or_else(invalid)?;
+                    let side = if file > king.file() {
+                        CastleSide::King
+                    } else {
+                        CastleSide::Queen
+                    };
+                    let rook = back_rank(board, color, Piece::Rook)
+                        .into_iter()
+                        .find(|rook| rook.file() == file);
+                    (side, rook)
+                }
+                _ => return Err(invalid()),
+            };
+            let rook = rook.ok_or_else(invalid)?;
+            if rights.has(color, side) {
+                return Err(invalid());
+            }
+            Ok(rights
+                .with_king(color, &king)
+                .grant_rook(color, side, &rook))
+        })
+}
+
+/// Write castling rights in X-FEN form, or in Shredder-FEN form with every
+/// rook given by its file
+///
+/// X-FEN is the same as standard FEN unless a side has two rooks on one
+/// wing and may castle with the inner one.
+fn castling_to_fen(rights: &CastlingRights, board: &Board, shredder: bool) -> String {
+    let fen = [Color::White, Color::Black]
+        .iter()
+        .flat_map(|color| CASTLE_SIDES.iter().map(move |side| (*color, *side)))
+        .filter_map(|(color, side)| {
+            let rook = rights.rook(color, side)?;
+            let outermost = outermost_rook(board, color, &rights.king(color), side);
+            if !shredder && outermost == Some(rook) {
+                Some(castling_letter(color, side))
+            } else {
+                Some(file_letter(color, &rook))
+            }
+        })
+        .collect::<String>();
+    if fen.is_empty() {
+        "-".to_string()
+    } else {
+        fen
+    }
+}
+
+/// Parse an en passant target, which must be on the rank the opponent's
+/// pawn just skipped
+fn parse_en_passant(field: &str, turn: Color) -> Result<Option<Square>, FenError> {
+    if field == "-" {
+        return Ok(None);
+    }
+    let expected_rank = match turn {
+        Color::White => Rank::Sixth,
+        Color::Black => Rank::Third,
+    };
+    match field.parse::<Square>() {
+        Ok(square) if square.rank() == expected_rank && field == field.to_ascii_lowercase() => {
+            Ok(Some(square))
+        }
+        _ => Err(FenError::InvalidEnPassant(field.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+
+    #[test]
+    fn test_starting_position() {
+        let state = GameState::from_fen(STARTING_FEN).unwrap();
+        let fresh = GameState::fresh_game();
+        assert!(state.board().to_fen() == fresh.board().to_fen());
+        assert!(fresh.to_fen() == STARTING_FEN);
+        assert!(state.turn() == Color::White);
+        assert!(state.meta().castling() == CastlingRights::all());
+        assert!(state.legal_moves().len() == 20);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let fens = [
+            STARTING_FEN,
+            KIWIPETE,
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+            "r3k3/8/8/3pP3/8/8/8/4K2R w Kq d6 37 112",
+        ];
+        for fen in fens.iter() {
+            assert!(GameState::from_fen(fen).unwrap().to_fen() == *fen);
+        }
+    }
+
+    #[test]
+    fn test_shredder_fen() {
+        let state = GameState::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w HAha - 0 1").unwrap();
+        assert!(state.meta().castling() == CastlingRights::all());
+        assert!(state.to_fen() == "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+        assert!(state.to_shredder_fen() == "r3k2r/8/8/8/8/8/8/R3K2R w HAha - 0 1");
+
+        let fen = "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9";
+        let state = GameState::from_fen(fen).unwrap();
+        let castling = state.meta().castling();
+        assert!(castling.king(Color::White) == Square::G1);
+        assert!(castling.rook(Color::White, CastleSide::King) == Some(Square::H1));
+        assert!(castling.rook(Color::White, CastleSide::Queen) == Some(Square::F1));
+        assert!(castling.rook(Color::Black, CastleSide::Queen) == Some(Square::F8));
+        assert!(state.to_shredder_fen() == fen);
+        assert!(
+            state.to_fen() == "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w KQkq - 2 9"
+        );
+    }
+
+    #[test]
+    fn test_x_fen() {
+        // Two rooks on the king's wing, so the inner one needs its file
+        let state = GameState::from_fen("rk2r2r/8/8/8/8/8/8/RK2R2R w Kq - 0 1").unwrap();
+        let castling = state.meta().castling();
+        assert!(castling.rook(Color::White, CastleSide::King) == Some(Square::H1));
+        assert!(castling.rook(Color::Black, CastleSide::Queen) == Some(Square::A8));
+        assert!(!castling.has(Color::Black, CastleSide::King));
+        let state = GameState::from_fen("rk2r2r/8/8/8/8/8/8/RK2R2R w EQ - 0 1").unwrap();
+        let castling = state.meta().castling();
+        assert!(castling.rook(Color::White, CastleSide::King) == Some(Square::E1));
+        assert!(castling.rook(Color::White, CastleSide::Queen) == Some(Square::A1));
+        assert!(state.to_fen() == "rk2r2r/8/8/8/8/8/8/RK2R2R w EQ - 0 1");
+        assert!(state.to_shredder_fen() == "rk2r2r/8/8/8/8/8/8/RK2R2R w EA - 0 1");
+    }
+
+    #[test]
+    fn test_chess960_castling_moves() {
+        let castle = |fen| {
+            let state = GameState::from_fen(fen).unwrap();
+            let mv = state
+                .legal_moves()
+                .into_iter()
+                .find(|mv| mv.is_castle())
+                .unwrap();
+            let next = state.apply_move(&mv);
+            assert!(next.meta().castling().is_empty());
+            (mv, next.board().to_fen())
+        };
+        let (mv, placement) = castle("6k1/8/8/8/8/8/8/RK6 w A - 0 1");
+        assert!(mv.from() == Square::B1 && mv.to() == Square::A1);
+        assert!(placement == "6k1/8/8/8/8/8/8/2KR4");
+        // The king lands on the rook's square and the rook on the king's
+        let (mv, placement) = castle("7k/8/8/8/8/8/8/5KR1 w G - 0 1");
+        assert!(mv.from() == Square::F1 && mv.to() == Square::G1);
+        assert!(placement == "7k/8/8/8/8/8/8/5RK1");
+    }
+
+    #[test]
+    fn test_missing_clocks() {
+        let state = "4k3/8/8/8/8/8/8/4K2R b K -".parse::<GameState>().unwrap();
+        assert!(state.turn() == Color::Black);
+        assert!(state.meta().halfmove_clock() == 0);
+        assert!(state.meta().fullmove_number() == 1);
+        assert!(state.to_fen() == "4k3/8/8/8/8/8/8/4K2R b K - 0 1");
+    }
+
+    #[test]
+    fn test_fields() {
+        let state = GameState::from_fen("4k3/8/8/8/3Pp3/8/8/4K3 b - d3 5 40").unwrap();
+        assert!(state.board().occupant(&Square::D4) == Some((Piece::Pawn, Color::White)));
+        assert!(state.board().occupant(&Square::E4) == Some((Piece::Pawn, Color::Black)));
+        assert!(state.board().occupant(&Square::E8) == Some((Piece::King, Color::Black)));
+        assert!(state.meta().castling().is_empty());
+        assert!(state.meta().en_passant() == Some(Square::D3));
+        assert!(state.meta().halfmove_clock() == 5);
+        assert!(state.meta().fullmove_number() == 40);
+    }
+
+    #[test]
+    fn test_board_errors() {
+        let error = |placement| Board::from_fen(placement).unwrap_err();
+        assert!(error("8/8/8/8") == FenError::RankCount(4));
+        assert!(
+            error("4k3/8/8/8/8/8/8/4K4")
+                == FenError::RankLength {
+                    rank: 1,
+                    squares: 9
+                }
+        );
+        assert!(
+            error("4k3/7/8/8/8/8/8/4K3")
+                == FenError::RankLength {
+                    rank: 7,
+                    squares: 7
+                }
+        );
+        assert!(
+            error("4k3/8/8/3x4/8/8/8/4K3")
+                == FenError::InvalidPiece {
+                    rank: 5,
+                    character: 'x'
+                }
+        );
+        assert!(
+            error("4k3/8/8/08/8/8/8/4K3")
+                == FenError::InvalidPiece {
+                    rank: 5,
+                    character: '0'
+                }
+        );
+        assert!(
+            error("8/8/8/8/8/8/8/4K3")
+                == FenError::KingCount {
+                    color: Color::Black,
+                    count: 0
+                }
+        );
+        assert!(
+            error("4k3/8/8/8/8/8/8/K3K3")
+                == FenError::KingCount {
+                    color: Color::White,
+                    count: 2
+                }
+        );
+        assert!(
+            error("4k3/8/8/8/P7/8/PPPPPPPP/4K3")
+                == FenError::TooManyPawns {
+                    color: Color::White,
+                    count: 9
+                }
+        );
+        // Far more moves than a move list holds, if it were allowed
+        assert!(
+            error("Q1QQQQQQ/nQ5Q/Q6Q/Q6Q/Q6Q/3QQ2Q/QQ4QQ/KnQQQQ1k")
+                == FenError::TooManyPieces {
+                    color: Color::White,
+                    count: 27
+                }
+        );
+        assert!(
+            error("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNQ")
+                == FenError::TooManyPromotions {
+                    color: Color::White,
+                    count: 1
+                }
+        );
+        assert!(error("4k3/8/8/8/8/44/8/4K3") == FenError::ConsecutiveDigits { rank: 3 });
+        assert!(error("4k2P/8/8/8/8/8/8/4K3") == FenError::PawnOnBackRank(Square::H8));
+        assert!(error("4k3/8/8/8/8/8/8/p3K3") == FenError::PawnOnBackRank(Square::A1));
+        // Promoted pieces are fine with pawns missing to account for them
+        assert!(Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/1PPPPPPP/RNBQKBNQ").is_ok());
+    }
+
+    #[test]
+    fn test_state_errors() {
+        let error = |fen| GameState::from_fen(fen).err().unwrap();
+        assert!(error("4k3/8/8/8/8/8/8/4K3 w -") == FenError::FieldCount(3));
+        assert!(error("4k3/8/8/8/8/8/8/4K3 w - - 0") == FenError::FieldCount(5));
+        assert!(
+            error("4k3/8/8/8/8/8/8/4K3 x - - 0 1") == FenError::InvalidSideToMove("x".to_string())
+        );
+        assert!(
+            error("4k3/8/8/8/8/8/8/4R1K1 w - - 0 1") == FenError::OpponentInCheck(Color::Black)
+        );
+        assert!(GameState::from_fen("4k3/8/8/8/8/8/8/4R1K1 b - - 0 1").is_ok());
+        assert!(
+            error("4k3/8/8/8/8/8/8/4K3 w KK - 0 1") == FenError::InvalidCastling("KK".to_string())
+        );
+        assert!(
+            error("4k3/8/8/8/8/8/8/4K3 w Kx - 0 1") == FenError::InvalidCastling("Kx".to_string())
+        );
+        assert!(
+            error("4k3/8/8/8/8/8/8/4K3 w K - 0 1") == FenError::InvalidCastling("K".to_string())
+        );
+        assert!(
+            error("4k3/8/8/8/8/8/4K3/7R w K - 0 1") == FenError::InvalidCastling("K".to_string())
+        );
+        assert!(
+            error("4k3/8/8/8/8/8/8/R3K2R w KH - 0 1")
+                == FenError::InvalidCastling("KH".to_string())
+        );
+        assert!(
+            error("4k3/8/8/8/8/8/8/R3K2R w B - 0 1") == FenError::InvalidCastling("B".to_string())
+        );
+        assert!(
+            error("4k3/8/8/8/8/8/8/4K3 w - e3 0 1") == FenError::InvalidEnPassant("e3".to_string())
+        );
+        assert!(
+            error("4k3/8/8/8/8/8/8/4K3 w - i6 0 1") == FenError::InvalidEnPassant("i6".to_string())
+        );
+        assert!(
+            error("4k3/8/8/8/8/8/8/4K3 w - - -1 1")
+                == FenError::InvalidHalfmoveClock("-1".to_string())
+        );
+        assert!(
+            error("4k3/8/8/8/8/8/8/4K3 w - - 0 0")
+                == FenError::InvalidFullmoveNumber("0".to_string())
+        );
+    }
+
+    #[test]
+    fn test_error_messages() {
+        assert!(
+            FenError::InvalidPiece {
+                rank: 5,
+                character: 'x'
+            }
+            .to_string()
+                == "invalid piece 'x' on rank 5"
+        );
+        assert!(FenError::FieldCount(2).to_string() == "expected 4 or 6 fields, found 2");
+    }
+}
@@ -1,12 +1,637 @@
-//! Tracking state snapshots of a game
+//! The state of a game and the moves which led to it
+//!
+//! A `GameState` owns its position and a history of the moves played with
+//! `push`, each with what's needed to take it back, so the positions before
+//! it can be revisited by popping moves or walked without changing anything.
 
-use crate::board::{Board, Color};
+use crate::bitboard::BitBoard;
+use crate::board::{Board, Color, Piece};
+use crate::castling::CastlingRights;
+use crate::moves::{Move, MoveKind, MoveList, UciError};
+use crate::square::{Square, SQUARES};
+use crate::traits::{Movable, Occupied};
+use crate::zobrist;
 
-struct StateMeta {}
+/// State beyond the piece placement needed to know which moves are legal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StateMeta {
+    castling: CastlingRights,
+    en_passant: Option<Square>,
+    halfmove_clock: u32,
+    fullmove_number: u32,
+}
+impl StateMeta {
+    /// Construct metadata for the first move of a game
+    pub const fn new(castling: CastlingRights, en_passant: Option<Square>) -> Self {
+        Self {
+            castling,
+            en_passant,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+        }
+    }
+    /// Return the same metadata with the given move clocks
+    pub const fn with_clocks(self, halfmove_clock: u32, fullmove_number: u32) -> Self {
+        Self {
+            halfmove_clock,
+            fullmove_number,
+            ..self
+        }
+    }
+    /// The castling rights remaining to each side
+    pub const fn castling(&self) -> CastlingRights {
+        self.castling
+    }
+    /// The square skipped by a pawn double push on the previous move
+    pub const fn en_passant(&self) -> Option<Square> {
+        self.en_passant
+    }
+    /// Half-moves since the last capture or pawn move
+    pub const fn halfmove_clock(&self) -> u32 {
+        self.halfmove_clock
+    }
+    /// The number of the current full move, starting at 1 and increasing
+    /// after each of black's moves
+    pub const fn fullmove_number(&self) -> u32 {
+        self.fullmove_number
+    }
+}
 
-struct GameState<'a> {
-    previous: Option<&'a GameState<'a>>,
+/// What's needed to take back a move made in place
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Undo {
+    mv: Move,
+    captured: Option<Piece>,
+    meta: StateMeta,
+    zobrist: u64,
+}
+impl Undo {
+    /// The move to be taken back
+    pub const fn mv(&self) -> Move {
+        self.mv
+    }
+    /// The piece the move captured, if any
+    pub const fn captured(&self) -> Option<Piece> {
+        self.captured
+    }
+    /// Castling rights, clocks and so on before the move
+    pub const fn meta(&self) -> &StateMeta {
+        &self.meta
+    }
+    /// The Zobrist key of the position before the move
+    pub const fn zobrist(&self) -> u64 {
+        self.zobrist
+    }
+}
+
+/// A position, the side to move, and the moves played to reach it
+#[derive(Debug, Clone)]
+pub struct GameState {
     board: Board,
     turn: Color,
     meta: StateMeta,
+    zobrist: u64,
+    history: Vec<Undo>,
+}
+impl GameState {
+    /// Construct a new game state with no history
+    pub const fn new(board: Board, turn: Color, meta: StateMeta) -> Self {
+        let zobrist = Self::compute_zobrist(&board, turn, &meta);
+        Self {
+            board,
+            turn,
+            meta,
+            zobrist,
+            history: Vec::new(),
+        }
+    }
+    /// Construct a game state for the typical starting position
+    pub const fn fresh_game() -> Self {
+        Self::new(
+            Board::fresh_game(),
+            Color::White,
+            StateMeta::new(CastlingRights::all(), None),
+        )
+    }
+    /// The current board
+    pub const fn board(&self) -> &Board {
+        &self.board
+    }
+    /// The side to move
+    pub const fn turn(&self) -> Color {
+        self.turn
+    }
+    /// Castling rights, clocks, and so on
+    pub const fn meta(&self) -> &StateMeta {
+        &self.meta
+    }
+    /// The Zobrist key for the position, including the side to move,
+    /// castling rights and any en passant target that can be used
+    pub const fn zobrist(&self) -> u64 {
+        self.zobrist
+    }
+    /// The moves pushed so far, oldest first, with the key and metadata of
+    /// the position each was played from
+    pub fn history(&self) -> &[Undo] {
+        &self.history
+    }
+    /// The moves pushed so far, oldest first
+    pub fn moves(&self) -> impl DoubleEndedIterator<Item = Move> + '_ {
+        self.history.iter().map(|undo| undo.mv)
+    }
+    /// The positions before each move pushed, most recent first
+    ///
+    /// Each is given without a history of its own.
+    pub fn past_positions(&self) -> PastPositions<'_> {
+        PastPositions {
+            state: self.without_history(),
+            history: &self.history,
+        }
+    }
+    /// A copy of the current position, without the moves which led to it
+    pub fn without_history(&self) -> Self {
+        Self {
+            board: self.board.clone(),
+            turn: self.turn,
+            meta: self.meta,
+            zobrist: self.zobrist,
+            history: Vec::new(),
+        }
+    }
+    /// Play a move, adding it to the history
+    ///
+    /// The move is assumed to be legal.
+    pub fn push(&mut self, mv: &Move) {
+        let undo = self.make_move(mv);
+        self.history.push(undo);
+    }
+    /// Take back the last move pushed, returning it
+    pub fn pop(&mut self) -> Option<Move> {
+        let undo = self.history.pop()?;
+        self.unmake_move(undo);
+        Some(undo.mv)
+    }
+    /// All pseudo-legal moves for the side to move
+    pub fn pseudo_legal_moves(&self) -> MoveList {
+        let mut moves = self.board.pseudo_legal_moves(self.turn);
+        self.extend_special_moves(&mut moves);
+        moves
+    }
+    /// All legal moves for the side to move
+    pub fn legal_moves(&self) -> MoveList {
+        let mut moves = self.board.legal_moves(self.turn);
+        self.extend_special_moves(&mut moves);
+        moves
+    }
+    /// Find the legal move described in UCI long algebraic notation
+    ///
+    /// Castling may be given as the king's move (`e1g1`) or as the king
+    /// taking its own rook (`e1h1`). Where a king could make an ordinary
+    /// move to the square it would castle to, the ordinary move is meant.
+    pub fn parse_uci(&self, uci: &str) -> Result<Move, UciError> {
+        let parsed = uci.parse::<Move>()?;
+        let moves = self
+            .legal_moves()
+            .into_iter()
+            .filter(|mv| mv.from() == parsed.from() && mv.promotion() == parsed.promotion())
+            .collect::<Vec<Move>>();
+        let castle_to = |mv: &Move| match mv.kind() {
+            MoveKind::Castle(side) => Some(side.king_destination(&mv.from())),
+            _ => None,
+        };
+        moves
+            .iter()
+            .find(|mv| mv.to() == parsed.to())
+            .or_else(|| moves.iter().find(|mv| castle_to(mv) == Some(parsed.to())))
+            .copied()
+            .ok_or_else(|| UciError::Illegal(uci.to_string()))
+    }
+    /// Whether the side to move is in check
+    pub fn in_check(&self) -> bool {
+        self.board.in_check(self.turn)
+    }
+    /// The pieces giving check to the side to move
+    pub fn checkers(&self) -> BitBoard {
+        self.board.checkers(self.turn)
+    }
+    /// The pieces of the side to move pinned against their king
+    pub fn pinned(&self) -> BitBoard {
+        self.board.pinned(self.turn)
+    }
+    /// Add the castling and en passant moves, which depend on state beyond
+    /// the board
+    fn extend_special_moves(&self, moves: &mut MoveList) {
+        self.board
+            .castling_moves(self.turn, &self.meta.castling, moves);
+        if let Some(target) = self.meta.en_passant {
+            self.board.en_passant_moves(self.turn, &target, moves);
+        }
+    }
+    /// Return the state following this one after a move, with the move
+    /// added to its history
+    ///
+    /// The move is assumed to be legal. The history is copied, so this takes
+    /// time in the length of the game; use `without_history` first if the
+    /// history isn't needed, or `make_move` to work in place.
+    pub fn apply_move(&self, mv: &Move) -> Self {
+        let mut history = self.history.clone();
+        history.push(Undo {
+            mv: *mv,
+            captured: self
+                .board
+                .occupant(&mv.captured_square())
+                .and_then(|(piece, color)| (color != self.turn).then_some(piece)),
+            meta: self.meta,
+            zobrist: self.zobrist,
+        });
+        let mut next = Self {
+            board: self.board.apply_move(mv),
+            turn: self.turn.opponent(),
+            meta: self.meta_after(mv),
+            zobrist: self.zobrist_without(mv),
+            history,
+        };
+        next.finish_zobrist(mv);
+        next
+    }
+    /// Apply a move in place, returning what's needed to take it back
+    ///
+    /// Unlike `push`, the move isn't added to the history, which suits a
+    /// search making and unmaking moves of its own.
+    ///
+    /// The move is assumed to be legal. Debug builds panic if the side to
+    /// move has no piece on its from square, rather than passing the turn
+    /// without moving anything.
+    pub fn make_move(&mut self, mv: &Move) -> Undo {
+        debug_assert!(
+            self.board.pieces(self.turn).occupied(&mv.from()),
+            "no piece of the side to move on {}",
+            mv.from()
+        );
+        let zobrist = self.zobrist_without(mv);
+        let meta = self.meta_after(mv);
+        let undo = Undo {
+            mv: *mv,
+            captured: self.board.make_move(mv),
+            meta: self.meta,
+            zobrist: self.zobrist,
+        };
+        self.turn = self.turn.opponent();
+        self.meta = meta;
+        self.zobrist = zobrist;
+        self.finish_zobrist(mv);
+        undo
+    }
+    /// Take back the last move made with `make_move`
+    pub fn unmake_move(&mut self, undo: Undo) {
+        self.board.unmake_move(&undo.mv, undo.captured);
+        self.turn = self.turn.opponent();
+        self.meta = undo.meta;
+        self.zobrist = undo.zobrist;
+    }
+    /// Castling rights, clocks and en passant target after a move
+    fn meta_after(&self, mv: &Move) -> StateMeta {
+        StateMeta {
+            castling: self.meta.castling.update_for_move(&mv.from(), &mv.to()),
+            en_passant: self.en_passant_after(mv),
+            halfmove_clock: self.halfmove_clock_after(mv),
+            fullmove_number: match self.turn {
+                Color::White => self.meta.fullmove_number,
+                Color::Black => self.meta.fullmove_number + 1,
+            },
+        }
+    }
+    /// This position's Zobrist key, less everything a move changes or
+    /// takes away, and with the moving pieces already moved
+    fn zobrist_without(&self, mv: &Move) -> u64 {
+        self.zobrist
+            ^ zobrist::move_key(&self.board, mv)
+            ^ zobrist::side_key(Color::Black)
+            ^ zobrist::castling_key(&self.meta.castling)
+            ^ zobrist::en_passant_key(&self.board, self.turn, self.meta.en_passant)
+    }
+    /// Add the parts of the Zobrist key which depend on the position after
+    /// a move, checking the key against a full recompute in debug builds
+    fn finish_zobrist(&mut self, mv: &Move) {
+        self.zobrist ^= zobrist::castling_key(&self.meta.castling)
+            ^ zobrist::en_passant_key(&self.board, self.turn, self.meta.en_passant);
+        debug_assert_eq!(
+            self.zobrist,
+            Self::compute_zobrist(&self.board, self.turn, &self.meta),
+            "incremental Zobrist key is wrong after {:?}",
+            mv
+        );
+    }
+    /// The Zobrist key for a position, computed from scratch
+    const fn compute_zobrist(board: &Board, turn: Color, meta: &StateMeta) -> u64 {
+        board.zobrist()
+            ^ zobrist::side_key(turn)
+            ^ zobrist::castling_key(&meta.castling)
+            ^ zobrist::en_passant_key(board, turn, meta.en_passant)
+    }
+    /// The halfmove clock after a move, which captures and pawn moves reset
+    fn halfmove_clock_after(&self, mv: &Move) -> u32 {
+        let pawn_move = self
+            .board
+            .pieces(self.turn)
+            .piece_board(Piece::Pawn)
+            .occupied(&mv.from());
+        if pawn_move || mv.is_capture() {
+            0
+        } else {
+            self.meta.halfmove_clock + 1
+        }
+    }
+    /// The en passant target created by a move, if it's a pawn double push
+    fn en_passant_after(&self, mv: &Move) -> Option<Square> {
+        if mv.is_double_push() {
+            Some(SQUARES[(mv.from() as usize + mv.to() as usize) / 2])
+        } else {
+            None
+        }
+    }
+}
+
+/// Walks back through the positions before each move of a game
+pub struct PastPositions<'a> {
+    state: GameState,
+    history: &'a [Undo],
+}
+impl<'a> Iterator for PastPositions<'a> {
+    type Item = GameState;
+    fn next(&mut self) -> Option<Self::Item> {
+        let (undo, rest) = self.history.split_last()?;
+        self.history = rest;
+        self.state.unmake_move(*undo);
+        Some(self.state.clone())
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.history.len(), Some(self.history.len()))
+    }
+}
+impl<'a> ExactSizeIterator for PastPositions<'a> {}
+
+#[cfg(test)]
+impl GameState {
+    /// Push a sequence of moves given in SAN, for setting up tests
+    pub(crate) fn play(&mut self, sans: &[&str]) {
+        for san in sans.iter() {
+            let mv = self.parse_san(san).unwrap();
+            self.push(&mv);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::castling::CastleSide;
+    use crate::fen::STARTING_FEN;
+    use crate::moves::MoveKind;
+
+    #[test]
+    fn test_fresh_game_moves() {
+        assert!(GameState::fresh_game().legal_moves().len() == 20);
+    }
+
+    #[test]
+    fn test_apply_move_records_history() {
+        let state = GameState::fresh_game();
+        let next = state.apply_move(&Move::new(&Square::E2, &Square::E4));
+        assert!(next.turn() == Color::Black);
+        assert!(state.history().is_empty());
+        assert!(next.moves().collect::<Vec<Move>>() == [Move::new(&Square::E2, &Square::E4)]);
+        assert!(next.history()[0].zobrist() == state.zobrist());
+    }
+
+    #[test]
+    fn test_push_and_pop() {
+        let mut state = GameState::fresh_game();
+        let start = state.to_fen();
+        let mut fens = vec![start.clone()];
+        for san in ["e4", "d5", "exd5", "Qxd5", "Nc3"].iter() {
+            let mv = state.parse_san(san).unwrap();
+            state.push(&mv);
+            fens.push(state.to_fen());
+        }
+        assert!(state.history().len() == 5);
+        assert!(state.history()[1].captured().is_none());
+        assert!(state.history()[2].captured() == Some(Piece::Pawn));
+        // Pushing matches applying, history included
+        let applied = state.moves().fold(GameState::fresh_game(), |applied, mv| {
+            applied.apply_move(&mv)
+        });
+        assert!(applied.history() == state.history());
+        assert!(applied.zobrist() == state.zobrist());
+
+        let past = state
+            .past_positions()
+            .map(|position| position.to_fen())
+            .collect::<Vec<String>>();
+        assert!(past.len() == 5);
+        assert!(past.iter().eq(fens.iter().rev().skip(1)));
+        assert!(state.to_fen() == fens[5]);
+        let copy = state.without_history();
+        assert!(copy.history().is_empty());
+        assert!(copy.to_fen() == fens[5] && copy.zobrist() == state.zobrist());
+
+        let last = state.pop().unwrap();
+        assert!(state.to_san(&last) == "Nc3");
+        assert!(state.to_fen() == fens[4]);
+        while state.pop().is_some() {}
+        assert!(state.to_fen() == start);
+        assert!(state.zobrist() == GameState::fresh_game().zobrist());
+        assert!(state.pop().is_none());
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "no piece of the side to move on E4")]
+    fn test_make_move_from_empty_square() {
+        let mut state = GameState::fresh_game();
+        state.make_move(&Move::new(&Square::E4, &Square::E2));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "no piece of the side to move on E7")]
+    fn test_make_move_for_wrong_side() {
+        let mut state = GameState::fresh_game();
+        state.push(&Move::new(&Square::E7, &Square::E5));
+    }
+
+    #[test]
+    fn test_castling_rights_follow_moves() {
+        let state = GameState::fresh_game();
+        let state = state.apply_move(&Move::new(&Square::G1, &Square::F3));
+        let state = state.apply_move(&Move::new(&Square::A7, &Square::A5));
+        let state = state.apply_move(&Move::new(&Square::G2, &Square::G3));
+        let state = state.apply_move(&Move::new(&Square::A8, &Square::A6));
+        let state = state.apply_move(&Move::new(&Square::F1, &Square::G2));
+        let state = state.apply_move(&Move::new(&Square::A6, &Square::B6));
+        let castling = state.meta().castling();
+        assert!(castling.has(Color::White, CastleSide::King));
+        assert!(castling.has(Color::White, CastleSide::Queen));
+        assert!(castling.has(Color::Black, CastleSide::King));
+        assert!(!castling.has(Color::Black, CastleSide::Queen));
+
+        let castles = state
+            .legal_moves()
+            .into_iter()
+            .filter(|mv| mv.kind() == MoveKind::Castle(CastleSide::King))
+            .collect::<Vec<_>>();
+        assert!(castles.len() == 1);
+        let state = state.apply_move(&castles[0]);
+        assert!(state.board().occupant(&Square::G1).is_some());
+        assert!(state.board().occupant(&Square::F1).is_some());
+        assert!(!state.meta().castling().has(Color::White, CastleSide::King));
+        assert!(!state.meta().castling().has(Color::White, CastleSide::Queen));
+    }
+
+    #[test]
+    fn test_clocks_follow_moves() {
+        let state = GameState::fresh_game();
+        let state = state.apply_move(&Move::new(&Square::G1, &Square::F3));
+        assert!(state.meta().halfmove_clock() == 1);
+        assert!(state.meta().fullmove_number() == 1);
+        let state = state.apply_move(&Move::new(&Square::G8, &Square::F6));
+        assert!(state.meta().halfmove_clock() == 2);
+        assert!(state.meta().fullmove_number() == 2);
+        let state = state.apply_move(&Move::double_push(&Square::E2, &Square::E4));
+        assert!(state.meta().halfmove_clock() == 0);
+        let state = state.apply_move(&Move::capture(&Square::F6, &Square::E4));
+        assert!(state.meta().halfmove_clock() == 0);
+        assert!(state.meta().fullmove_number() == 3);
+        let state = state.apply_move(&Move::new(&Square::B1, &Square::C3));
+        assert!(state.meta().halfmove_clock() == 1);
+    }
+
+    #[test]
+    fn test_parse_uci() {
+        let state = GameState::fresh_game();
+        let mv = state.parse_uci("e2e4").unwrap();
+        assert!(mv.is_double_push());
+        assert!(state.parse_uci("e2e5") == Err(UciError::Illegal("e2e5".to_string())));
+        assert!(state.parse_uci("e2") == Err(UciError::Invalid("e2".to_string())));
+
+        let state = GameState::from_fen("r3k2r/1P6/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        let standard = state.parse_uci("e1g1").unwrap();
+        assert!(standard.kind() == MoveKind::Castle(CastleSide::King));
+        assert!(state.parse_uci("e1h1") == Ok(standard));
+        assert!(state.parse_uci("e1c1") == state.parse_uci("e1a1"));
+        let promotion = state.parse_uci("b7a8q").unwrap();
+        assert!(promotion.is_capture() && promotion.promotion() == Some(Piece::Queen));
+        assert!(state.parse_uci("b7a8") == Err(UciError::Illegal("b7a8".to_string())));
+        for mv in state.legal_moves().into_iter() {
+            assert!(state.parse_uci(&mv.to_uci(false)) == Ok(mv));
+            assert!(state.parse_uci(&mv.to_uci(true)) == Ok(mv));
+        }
+
+        // The king can step to g1 itself, so only e1h1 castles
+        let state = GameState::from_fen("4k3/8/8/8/8/8/8/5K1R w H - 0 1").unwrap();
+        assert!(!state.parse_uci("f1g1").unwrap().is_castle());
+        assert!(state.parse_uci("f1h1").unwrap().is_castle());
+    }
+
+    #[test]
+    fn test_en_passant_target_after_double_push() {
+        let state = GameState::fresh_game();
+        let state = state.apply_move(&Move::double_push(&Square::E2, &Square::E4));
+        assert!(state.meta().en_passant() == Some(Square::E3));
+        let state = state.apply_move(&Move::new(&Square::G8, &Square::F6));
+        assert!(state.meta().en_passant().is_none());
+        let state = state.apply_move(&Move::new(&Square::E4, &Square::E5));
+        let state = state.apply_move(&Move::double_push(&Square::D7, &Square::D5));
+        assert!(state.meta().en_passant() == Some(Square::D6));
+
+        let captures = state
+            .legal_moves()
+            .into_iter()
+            .filter(|mv| mv.kind() == MoveKind::EnPassant)
+            .collect::<Vec<_>>();
+        assert!(captures.len() == 1);
+        let state = state.apply_move(&captures[0]);
+        assert!(state.board().occupant(&Square::D5).is_none());
+        assert!(state.board().occupant(&Square::D6) == Some((Piece::Pawn, Color::White)));
+    }
+
+    #[test]
+    fn test_make_move() {
+        let fen = "r3k2r/1P6/8/3pP3/8/8/8/R3K2R w KQkq d6 5 20";
+        let mut state = GameState::from_fen(fen).unwrap();
+        let en_passant = state.parse_san("exd6").unwrap();
+        let undo = state.make_move(&en_passant);
+        assert!(undo.captured() == Some(Piece::Pawn));
+        assert!(state.board().occupant(&Square::D5).is_none());
+        assert!(state.meta().en_passant().is_none());
+        assert!(state.meta().halfmove_clock() == 0);
+        state.unmake_move(undo);
+        assert!(state.to_fen() == fen);
+
+        let promotion = state.parse_san("bxa8=Q").unwrap();
+        let undo = state.make_move(&promotion);
+        assert!(undo.captured() == Some(Piece::Rook));
+        assert!(state.to_fen() == "Q3k2r/8/8/3pP3/8/8/8/R3K2R b KQk - 0 20");
+        state.unmake_move(undo);
+        assert!(state.to_fen() == fen);
+
+        let castle = state.parse_san("O-O").unwrap();
+        let undo = state.make_move(&castle);
+        assert!(undo.captured().is_none());
+        assert!(state.to_fen() == "r3k2r/1P6/8/3pP3/8/8/8/R4RK1 b kq - 6 20");
+        state.unmake_move(undo);
+        assert!(state.to_fen() == fen);
+    }
+
+    /// Everything making and unmaking a move must keep in step
+    fn snapshot(state: &GameState) -> (String, u64, BitBoard, BitBoard) {
+        let all = |color| state.board().pieces(color).all();
+        (
+            state.to_fen(),
+            state.zobrist(),
+            all(Color::White),
+            all(Color::Black),
+        )
+    }
+
+    #[test]
+    fn test_make_unmake_identity() {
+        // A fixed xorshift sequence, so any failure can be played again
+        let mut seed = 0x9E37_79B9_7F4A_7C15u64;
+        let mut random = move || {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed as usize
+        };
+        let fens = [
+            STARTING_FEN,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+        ];
+        for fen in fens.iter() {
+            for _ in 0..40 {
+                let mut state = GameState::from_fen(fen).unwrap();
+                let mut history = Vec::new();
+                for _ in 0..60 {
+                    let moves = state.legal_moves();
+                    if moves.is_empty() {
+                        break;
+                    }
+                    let mv = moves[random() % moves.len()];
+                    let before = snapshot(&state);
+                    let expected = snapshot(&state.apply_move(&mv));
+                    let undo = state.make_move(&mv);
+                    assert!(snapshot(&state) == expected, "{} then {:?}", before.0, mv);
+                    history.push((undo, before));
+                }
+                while let Some((undo, before)) = history.pop() {
+                    state.unmake_move(undo);
+                    assert!(snapshot(&state) == before, "{} {:?}", before.0, undo.mv());
+                }
+            }
+        }
+    }
 }
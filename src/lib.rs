@@ -1,6 +1,21 @@
 #![allow(dead_code)]
+mod attacks;
 mod bitboard;
 mod board;
+mod castling;
+mod epd;
+mod fen;
 mod game_state;
+mod magic;
+mod material;
+mod movegen;
+mod moves;
+mod outcome;
+mod perft;
+#[cfg(all(feature = "bmi2", target_arch = "x86_64"))]
+mod pext;
+mod pgn;
+mod san;
 mod square;
 mod traits;
+mod zobrist;
@@ -0,0 +1,244 @@
+//! Magic bitboard tables for sliding piece attacks
+//!
+//! For each square, the occupancy of the squares which can block a slider
+//! (its relevant occupancy mask) is multiplied by a "magic" number and
+//! shifted down, giving a perfect hash into a table of precalculated
+//! attacks. Magic numbers are found by trial and error the first time the
+//! tables are needed, using a fixed seed so that every run finds the same
+//! ones.
+
+use std::sync::OnceLock;
+
+use crate::attacks::{slide, Direction, BISHOP_DIRECTIONS, ROOK_DIRECTIONS};
+use crate::bitboard::{BitBoard, NO_SQUARES};
+use crate::square::{Square, SQUARES};
+
+static TABLES: OnceLock<MagicTables> = OnceLock::new();
+
+/// Return the magic tables, generating them on first use
+pub fn tables() -> &'static MagicTables {
+    TABLES.get_or_init(MagicTables::generate)
+}
+
+/// The magic hashing parameters for a single square
+#[derive(Debug, Clone, Copy)]
+struct Magic {
+    /// Squares whose occupancy affects the attacks
+    mask: u64,
+    magic: u64,
+    /// 64 less the number of bits in the mask
+    shift: u32,
+    /// Where this square's attacks start in the shared attack table
+    offset: usize,
+}
+impl Magic {
+    const fn index(&self, occupancy: u64) -> usize {
+        self.offset + ((occupancy & self.mask).wrapping_mul(self.magic) >> self.shift) as usize
+    }
+}
+
+/// Magic parameters and attacks for rooks and bishops on every square
+pub struct MagicTables {
+    rooks: Vec<Magic>,
+    bishops: Vec<Magic>,
+    attacks: Vec<BitBoard>,
+}
+impl MagicTables {
+    /// Squares attacked by a rook on the given square
+    pub fn rook_attacks(&self, square: &Square, occupancy: &BitBoard) -> BitBoard {
+        self.attacks[self.rooks[*square as usize].index(occupancy.positions())]
+    }
+    /// Squares attacked by a bishop on the given square
+    pub fn bishop_attacks(&self, square: &Square, occupancy: &BitBoard) -> BitBoard {
+        self.attacks[self.bishops[*square as usize].index(occupancy.positions())]
+    }
+    /// Find magics for every square and fill in the attack table
+    fn generate() -> Self {
+        let mut rng = Rng::new(0x2545_F491_4F6C_DD1D);
+        let mut attacks = Vec::new();
+        let mut find = |directions: &[Direction]| {
+            SQUARES
+                .iter()
+                .map(|square| find_magic(square, directions, &mut rng, &mut attacks))
+                .collect::<Vec<Magic>>()
+        };
+        let rooks = find(&ROOK_DIRECTIONS);
+        let bishops = find(&BISHOP_DIRECTIONS);
+        Self {
+            rooks,
+            bishops,
+            attacks,
+        }
+    }
+}
+
+/// The squares which can block a slider on the given square
+///
+/// The last square of each ray is left out, since a piece there can't
+/// block anything further along.
+pub fn relevant_occupancy(square: &Square, directions: &[Direction]) -> BitBoard {
+    let from = BitBoard::from_square(square);
+    directions.iter().fold(NO_SQUARES, |mask, shift| {
+        let mut mask = mask;
+        let mut ray = shift(&from);
+        while !shift(&ray).is_empty() {
+            mask = mask.union(&ray);
+            ray = shift(&ray);
+        }
+        mask
+    })
+}
+
+/// Every subset of a mask, enumerated with the Carry-Rippler trick
+pub fn subsets(mask: u64) -> Vec<u64> {
+    let mut subsets = Vec::with_capacity(1 << mask.count_ones());
+    let mut subset = 0u64;
+    loop {
+        subsets.push(subset);
+        subset = subset.wrapping_sub(mask) & mask;
+        if subset == 0 {
+            break;
+        }
+    }
+    subsets
+}
+
+/// Search for a magic for one square, appending its attacks to the table
+fn find_magic(
+    square: &Square,
+    directions: &[Direction],
+    rng: &mut Rng,
+    attacks: &mut Vec<BitBoard>,
+) -> Magic {
+    let from = BitBoard::from_square(square);
+    let mask = relevant_occupancy(square, directions).positions();
+    let bits = mask.count_ones();
+    let occupancies = subsets(mask);
+    let reference = occupancies
+        .iter()
+        .map(|occupancy| slide(&from, directions, &BitBoard::new(*occupancy)))
+        .collect::<Vec<BitBoard>>();
+
+    let size = 1 << bits;
+    let mut table = vec![NO_SQUARES; size];
+    // The attempt on which each table entry was last written, so the table
+    // doesn't need clearing between attempts
+    let mut epoch = vec![0u32; size];
+    let mut attempt = 0;
+    loop {
+        let magic = rng.sparse();
+        // Good magics map the mask's bits into the top of the product
+        if (mask.wrapping_mul(magic) & 0xFF00_0000_0000_0000).count_ones() < 6 {
+            continue;
+        }
+        attempt += 1;
+        let candidate = Magic {
+            mask,
+            magic,
+            shift: 64 - bits,
+            offset: 0,
+        };
+        let collision = occupancies.iter().zip(reference.iter()).any(|(occ, atk)| {
+            let idx = candidate.index(*occ);
+            if epoch[idx] != attempt {
+                epoch[idx] = attempt;
+                table[idx] = *atk;
+                false
+            } else {
+                // Sharing an entry is fine if the attacks are the same
+                table[idx] != *atk
+            }
+        });
+        if !collision {
+            let offset = attacks.len();
+            attacks.extend(table);
+            return Magic {
+                offset,
+                ..candidate
+            };
+        }
+    }
+}
+
+/// A small xorshift generator, so magic search needs no dependencies
+struct Rng {
+    state: u64,
+}
+impl Rng {
+    const fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+    fn next(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+    /// A random number with relatively few bits set, which make good magics
+    fn sparse(&mut self) -> u64 {
+        self.next() & self.next() & self.next()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_relevant_occupancy() {
+        assert!(relevant_occupancy(&Square::A1, &ROOK_DIRECTIONS).count() == 12);
+        assert!(relevant_occupancy(&Square::E4, &ROOK_DIRECTIONS).count() == 10);
+        assert!(relevant_occupancy(&Square::A1, &BISHOP_DIRECTIONS).count() == 6);
+        assert!(relevant_occupancy(&Square::E4, &BISHOP_DIRECTIONS).count() == 9);
+        assert!(
+            relevant_occupancy(&Square::B7, &BISHOP_DIRECTIONS)
+                == BitBoard::from_squares(&vec![
+                    Square::C6,
+                    Square::D5,
+                    Square::E4,
+                    Square::F3,
+                    Square::G2
+                ])
+        );
+    }
+
+    #[test]
+    fn test_subsets() {
+        let mask = BitBoard::from_squares(&vec![Square::A1, Square::C3, Square::H8]).positions();
+        let subsets = subsets(mask);
+        assert!(subsets.len() == 8);
+        assert!(subsets.iter().all(|subset| subset & !mask == 0));
+        assert!(subsets.contains(&0));
+        assert!(subsets.contains(&mask));
+    }
+
+    #[test]
+    fn test_table_size() {
+        // Every square's table is exactly large enough for its mask
+        let tables = tables();
+        let total = |magics: &[Magic]| {
+            magics
+                .iter()
+                .map(|magic| 1usize << magic.mask.count_ones())
+                .sum::<usize>()
+        };
+        assert!(total(&tables.rooks) == 102_400);
+        assert!(total(&tables.bishops) == 5_248);
+        assert!(tables.attacks.len() == 102_400 + 5_248);
+    }
+
+    #[test]
+    fn test_every_rook_occupancy() {
+        let tables = tables();
+        for square in [Square::A1, Square::E4, Square::H7].iter() {
+            let from = BitBoard::from_square(square);
+            for occupancy in subsets(relevant_occupancy(square, &ROOK_DIRECTIONS).positions()) {
+                let occupancy = BitBoard::new(occupancy);
+                assert!(
+                    tables.rook_attacks(square, &occupancy)
+                        == slide(&from, &ROOK_DIRECTIONS, &occupancy)
+                );
+            }
+        }
+    }
+}
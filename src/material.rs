@@ -0,0 +1,244 @@
+//! Material signatures and mating material
+//!
+//! A position's material is the count of each kind of piece either side has,
+//! along with the colours of square its bishops stand on. That's enough to
+//! tell when neither side could ever mate, whatever the moves, which makes
+//! the position dead under the FIDE laws. The USCF rules for a loss on time
+//! are stricter, and let a player with only a minor piece or two knights
+//! escape with a draw even where a mate could still be helped along.
+
+use crate::bitboard::{BitBoard, DARK_SQUARES, LIGHT_SQUARES};
+use crate::board::{Board, Color, Piece, ALL_PIECES};
+use crate::square::SquareColor;
+use std::fmt;
+
+/// The colours of square a set of bishops stand on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BishopColors {
+    None,
+    One(SquareColor),
+    Both,
+}
+impl BishopColors {
+    pub const fn of(bishops: &BitBoard) -> Self {
+        match (
+            bishops.intersects(&LIGHT_SQUARES),
+            bishops.intersects(&DARK_SQUARES),
+        ) {
+            (false, false) => Self::None,
+            (true, false) => Self::One(SquareColor::White),
+            (false, true) => Self::One(SquareColor::Black),
+            (true, true) => Self::Both,
+        }
+    }
+    /// The colours covered by either set of bishops
+    pub const fn union(self, other: Self) -> Self {
+        match (self, other) {
+            (Self::None, colors) | (colors, Self::None) => colors,
+            (Self::One(a), Self::One(b)) if a as u8 == b as u8 => self,
+            _ => Self::Both,
+        }
+    }
+}
+
+/// The pieces each side has, without regard to where they stand
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Material {
+    counts: [[u8; 6]; 2],
+    bishops: [BishopColors; 2],
+}
+impl Material {
+    pub fn new(board: &Board) -> Self {
+        let mut counts = [[0; 6]; 2];
+        let mut bishops = [BishopColors::None; 2];
+        for color in [Color::White, Color::Black].iter() {
+            let pieces = board.pieces(*color);
+            for piece in ALL_PIECES.iter() {
+                counts[*color as usize][*piece as usize] =
+                    pieces.piece_board(*piece).board().count() as u8;
+            }
+            bishops[*color as usize] = BishopColors::of(&pieces.piece_board(Piece::Bishop).board());
+        }
+        Self { counts, bishops }
+    }
+    /// How many of a piece a side has
+    pub const fn count(&self, color: Color, piece: Piece) -> u8 {
+        self.counts[color as usize][piece as usize]
+    }
+    /// The colours of square a side's bishops stand on
+    pub const fn bishop_colors(&self, color: Color) -> BishopColors {
+        self.bishops[color as usize]
+    }
+    /// The signature of the material, like `KRPvKR`
+    ///
+    /// White's pieces come first, each side's in order of value from the
+    /// king down.
+    pub fn signature(&self) -> String {
+        let side = |color| {
+            ALL_PIECES
+                .iter()
+                .flat_map(|piece| {
+                    std::iter::repeat_n(piece.letter(), self.count(color, *piece) as usize)
+                })
+                .collect::<String>()
+        };
+        format!("{}v{}", side(Color::White), side(Color::Black))
+    }
+    /// Whether a side has nothing but its king and the given pieces
+    fn only(&self, color: Color, pieces: &[Piece]) -> bool {
+        ALL_PIECES
+            .iter()
+            .filter(|piece| **piece != Piece::King && !pieces.contains(piece))
+            .all(|piece| self.count(color, *piece) == 0)
+    }
+    /// Whether a side could mate by any sequence of legal moves
+    ///
+    /// This is the FIDE test, which allows for the other side helping: a
+    /// lone knight can mate a king hemmed in by its own rooks, bishops,
+    /// knights or pawns, and bishops all on one colour can mate when the
+    /// other side has a knight or pawn to block with. Only material is
+    /// considered, so a position which is dead because of how its pawns are
+    /// locked isn't spotted.
+    pub fn can_mate(&self, color: Color) -> bool {
+        let opponent = color.opponent();
+        if !self.only(color, &[Piece::Bishop, Piece::Knight]) {
+            return true;
+        }
+        if self.count(color, Piece::Knight) > 0 {
+            return self.count(color, Piece::Knight) + self.count(color, Piece::Bishop) > 1
+                || !self.only(opponent, &[Piece::Queen]);
+        }
+        if self.count(color, Piece::Bishop) > 0 {
+            let bishops = self
+                .bishop_colors(color)
+                .union(self.bishop_colors(opponent));
+            return bishops == BishopColors::Both
+                || self.count(opponent, Piece::Knight) > 0
+                || self.count(opponent, Piece::Pawn) > 0;
+        }
+        false
+    }
+    /// Whether neither side could mate, so the game is drawn
+    pub fn is_dead(&self) -> bool {
+        !self.can_mate(Color::White) && !self.can_mate(Color::Black)
+    }
+    /// Whether a side has enough material to win on time under USCF rules
+    ///
+    /// On top of the FIDE test, a lone minor piece never wins on time, nor
+    /// do two knights against a bare king. The rules make an exception when
+    /// the side has a forced mate, which needs a search to find and isn't
+    /// checked here.
+    pub fn can_win_on_time(&self, color: Color) -> bool {
+        let minors = self.count(color, Piece::Bishop) + self.count(color, Piece::Knight);
+        let two_knights = self.count(color, Piece::Knight) == 2
+            && self.only(color, &[Piece::Knight])
+            && self.only(color.opponent(), &[]);
+        self.can_mate(color)
+            && !(self.only(color, &[Piece::Bishop, Piece::Knight]) && minors <= 1)
+            && !two_knights
+    }
+}
+/// The signature, with whether the bishops share a colour when both sides
+/// have them, like `KBvKB same color`
+impl fmt::Display for Material {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.signature())?;
+        let (white, black) = (
+            self.bishop_colors(Color::White),
+            self.bishop_colors(Color::Black),
+        );
+        if white != BishopColors::None && black != BishopColors::None {
+            match white.union(black) {
+                BishopColors::Both => write!(f, " opposite colors")?,
+                _ => write!(f, " same color")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Board {
+    /// The material on the board
+    pub fn material(&self) -> Material {
+        Material::new(self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::game_state::GameState;
+
+    fn material(fen: &str) -> Material {
+        GameState::from_fen(fen).unwrap().board().material()
+    }
+
+    #[test]
+    fn test_signature() {
+        let start = GameState::fresh_game().board().material();
+        assert!(start.signature() == "KQRRBBNNPPPPPPPPvKQRRBBNNPPPPPPPP");
+        assert!(start.count(Color::Black, Piece::Pawn) == 8);
+        assert!(start.bishop_colors(Color::White) == BishopColors::Both);
+        assert!(start.to_string() == "KQRRBBNNPPPPPPPPvKQRRBBNNPPPPPPPP opposite colors");
+
+        let same = material("8/8/4k3/5b2/8/3K4/2B5/8 w - - 0 1");
+        assert!(same.bishop_colors(Color::White) == BishopColors::One(SquareColor::White));
+        assert!(same.to_string() == "KBvKB same color");
+        let opposite = material("8/8/4k3/5b2/8/3K4/8/4B3 w - - 0 1");
+        assert!(opposite.to_string() == "KBvKB opposite colors");
+        assert!(material("8/8/4k3/8/8/3KNN2/8/8 w - - 0 1").to_string() == "KNNvK");
+        assert!(material("8/8/4k3/2r5/8/3K3R/4P3/8 w - - 0 1").to_string() == "KRPvKR");
+    }
+
+    #[test]
+    fn test_dead_positions() {
+        let dead = [
+            "8/8/4k3/8/8/3K4/8/8 w - - 0 1",
+            "8/8/4k3/8/8/3KB3/8/8 w - - 0 1",
+            "8/8/4kn2/8/8/3K4/8/8 w - - 0 1",
+            "8/8/4k3/5b2/8/3K4/2B1B3/8 w - - 0 1",
+        ];
+        for fen in dead.iter() {
+            assert!(material(fen).is_dead(), "{}", fen);
+        }
+        let alive = [
+            "8/8/4k3/8/8/3KP3/8/8 w - - 0 1",
+            "8/8/4k3/8/8/3KNN2/8/8 w - - 0 1",
+            "8/8/4kn2/8/8/3KB3/8/8 w - - 0 1",
+            "8/8/4k3/5b2/8/3K4/8/4B3 w - - 0 1",
+        ];
+        for fen in alive.iter() {
+            assert!(!material(fen).is_dead(), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn test_can_mate() {
+        // A knight needs something other than queens to hem the king in
+        let knight_queen = material("8/8/4kq2/8/8/3KN3/8/8 w - - 0 1");
+        assert!(!knight_queen.can_mate(Color::White));
+        assert!(knight_queen.can_mate(Color::Black));
+        assert!(!knight_queen.is_dead());
+        assert!(material("8/8/4kr2/8/8/3KN3/8/8 w - - 0 1").can_mate(Color::White));
+        // Bishops on one colour need a knight or pawn to block with
+        let bishop_rook = material("8/8/4kr2/8/8/3KB3/8/8 w - - 0 1");
+        assert!(!bishop_rook.can_mate(Color::White));
+        assert!(material("8/8/4k1p1/8/8/3KB3/8/8 w - - 0 1").can_mate(Color::White));
+        assert!(material("8/8/4k3/8/8/3KBB2/8/8 w - - 0 1").can_mate(Color::White));
+        assert!(!material("8/8/4k3/8/8/2BKB3/8/8 w - - 0 1").can_mate(Color::White));
+    }
+
+    #[test]
+    fn test_win_on_time() {
+        let knight_pawn = material("8/8/4k1p1/8/8/3KN3/8/8 w - - 0 1");
+        assert!(knight_pawn.can_mate(Color::White));
+        assert!(!knight_pawn.can_win_on_time(Color::White));
+        assert!(knight_pawn.can_win_on_time(Color::Black));
+        let two_knights = material("8/8/4k3/8/8/3KNN2/8/8 w - - 0 1");
+        assert!(two_knights.can_mate(Color::White));
+        assert!(!two_knights.can_win_on_time(Color::White));
+        assert!(material("8/8/4k1p1/8/8/3KNN2/8/8 w - - 0 1").can_win_on_time(Color::White));
+        assert!(material("8/8/4k3/8/8/3KBN2/8/8 w - - 0 1").can_win_on_time(Color::White));
+        assert!(!material("8/8/4k3/8/8/3K4/8/8 w - - 0 1").can_win_on_time(Color::White));
+    }
+}
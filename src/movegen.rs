@@ -0,0 +1,906 @@
+//! Move generation
+//!
+//! Moves are generated from each side's piece boards using the attack
+//! lookups. Legal generation works out checks and pins up front, so that
+//! only moves which keep the king safe are produced.
+
+use crate::attacks::{
+    bishop_attacks, king_attacks, knight_attacks, pawn_attacks, rook_attacks, slide,
+    BISHOP_DIRECTIONS, ROOK_DIRECTIONS,
+};
+use crate::bitboard::{BitBoard, ALL_SQUARES, NO_SQUARES, RANK_1, RANK_3, RANK_6, RANK_8};
+use crate::board::{Board, Color, Piece, PROMOTION_PIECES};
+use crate::castling::{CastlingRights, CASTLE_SIDES};
+use crate::moves::{Move, MoveList};
+use crate::square::Square;
+use crate::traits::Occupied;
+
+/// Pawns reaching either back rank must promote
+const PROMOTION_RANKS: BitBoard = RANK_1.union(&RANK_8);
+
+/// The squares strictly between two squares sharing a rank, file or diagonal
+///
+/// Squares which are not aligned have nothing between them.
+fn between(a: &Square, b: &Square) -> BitBoard {
+    let a = BitBoard::from_square(a);
+    let b = BitBoard::from_square(b);
+    ROOK_DIRECTIONS
+        .iter()
+        .chain(BISHOP_DIRECTIONS.iter())
+        .map(|direction| slide(&a, &[*direction], &b))
+        .find(|ray| ray.intersects(&b))
+        .map(|ray| b.relative_complement(&ray))
+        .unwrap_or(NO_SQUARES)
+}
+
+/// Squares a pawn can move to, including captures of enemy pieces
+fn pawn_targets(from: &Square, color: Color, occupied: &BitBoard, enemy: &BitBoard) -> BitBoard {
+    let attacks = pawn_attacks(from, color);
+    let from = BitBoard::from_square(from);
+    let empty = occupied.complement();
+    let (single, double) = match color {
+        Color::White => {
+            let single = from.shift_north().intersection(&empty);
+            let double = single
+                .intersection(&RANK_3)
+                .shift_north()
+                .intersection(&empty);
+            (single, double)
+        }
+        Color::Black => {
+            let single = from.shift_south().intersection(&empty);
+            let double = single
+                .intersection(&RANK_6)
+                .shift_south()
+                .intersection(&empty);
+            (single, double)
+        }
+    };
+    single.union(&double).union(&attacks.intersection(enemy))
+}
+
+/// Squares the given piece could move to from a square, ignoring whether
+/// those squares hold friendly pieces.
+fn piece_targets(
+    piece: Piece,
+    color: Color,
+    from: &Square,
+    occupied: &BitBoard,
+    enemy: &BitBoard,
+) -> BitBoard {
+    match piece {
+        Piece::King => king_attacks(from),
+        Piece::Queen => rook_attacks(from, occupied).union(&bishop_attacks(from, occupied)),
+        Piece::Rook => rook_attacks(from, occupied),
+        Piece::Bishop => bishop_attacks(from, occupied),
+        Piece::Knight => knight_attacks(from),
+        Piece::Pawn => pawn_targets(from, color, occupied, enemy),
+    }
+}
+
+/// Limits placed on generated moves to keep them legal
+struct Restrictions {
+    /// Squares the king may move to
+    king: BitBoard,
+    /// Squares any other piece may move to
+    others: BitBoard,
+    /// Pinned pieces, along with the squares they may still move to
+    pins: Vec<(Square, BitBoard)>,
+}
+impl Restrictions {
+    /// No restrictions at all, for pseudo-legal generation
+    fn none() -> Self {
+        Self {
+            king: ALL_SQUARES,
+            others: ALL_SQUARES,
+            pins: Vec::new(),
+        }
+    }
+    /// The squares a piece on the given square may move to
+    fn for_square(&self, piece: Piece, from: &Square) -> BitBoard {
+        match piece {
+            Piece::King => self.king,
+            _ => self
+                .pins
+                .iter()
+                .find(|(pinned, _)| pinned == from)
+                .map(|(_, ray)| ray.intersection(&self.others))
+                .unwrap_or(self.others),
+        }
+    }
+}
+
+impl Board {
+    /// Generate all pseudo-legal moves for the given side
+    ///
+    /// Pseudo-legal moves follow the movement rules of each piece, but
+    /// may leave the moving side's king in check.
+    pub fn pseudo_legal_moves(&self, color: Color) -> MoveList {
+        self.generate_moves(color, &Restrictions::none())
+    }
+
+    /// Generate all legal moves for the given side
+    ///
+    /// Check evasions and pins are computed directly, so every move
+    /// returned leaves the moving side's king out of check.
+    pub fn legal_moves(&self, color: Color) -> MoveList {
+        let king = self.pieces(color).piece_board(Piece::King).board();
+        let king_square = match self.king_square(color) {
+            Some(square) => square,
+            // Without a king there is nothing to keep safe
+            None => return self.pseudo_legal_moves(color),
+        };
+        let occupied = self.occupied_squares();
+        let checkers = self.attackers(&king_square, color.opponent(), &occupied);
+
+        // The king is removed when finding attacked squares so that it
+        // can't "hide" from a slider by stepping back along its ray.
+        let danger = self.attacked_squares(color.opponent(), &king.relative_complement(&occupied));
+        let king_restriction = danger.complement();
+
+        let others = match checkers.count() {
+            0 => ALL_SQUARES,
+            // Capture the checker or block the check
+            1 => checkers.union(&between(&king_square, &checkers.occupied_squares()[0])),
+            // Double check, only the king may move
+            _ => NO_SQUARES,
+        };
+
+        let restrictions = Restrictions {
+            king: king_restriction,
+            others,
+            pins: self.pins(color, &king_square),
+        };
+        self.generate_moves(color, &restrictions)
+    }
+
+    /// Whether the given side's king is attacked
+    pub fn in_check(&self, color: Color) -> bool {
+        !self.checkers(color).is_empty()
+    }
+
+    /// Whether any piece of the given side attacks a square
+    pub fn is_square_attacked(&self, square: &Square, by: Color) -> bool {
+        !self
+            .attackers(square, by, &self.occupied_squares())
+            .is_empty()
+    }
+
+    /// The pieces of both sides attacking a square
+    ///
+    /// A piece defending one of its own side counts as attacking its square.
+    pub fn attackers_to(&self, square: &Square) -> BitBoard {
+        let occupied = self.occupied_squares();
+        self.attackers(square, Color::White, &occupied)
+            .union(&self.attackers(square, Color::Black, &occupied))
+    }
+
+    /// The enemy pieces giving check to the given side's king
+    pub fn checkers(&self, color: Color) -> BitBoard {
+        self.king_square(color).map_or(NO_SQUARES, |square| {
+            self.attackers(&square, color.opponent(), &self.occupied_squares())
+        })
+    }
+
+    /// The pieces of the given side pinned against their own king
+    ///
+    /// A pinned piece may still move along the line between the king and
+    /// the piece pinning it.
+    pub fn pinned(&self, color: Color) -> BitBoard {
+        self.king_square(color).map_or(NO_SQUARES, |square| {
+            self.pins(color, &square)
+                .iter()
+                .fold(NO_SQUARES, |pinned, (square, _)| pinned.set(square))
+        })
+    }
+
+    /// Add the legal castling moves for a side with the given rights to a
+    /// move list
+    ///
+    /// Every square the king and rook cross or land on must be empty, aside
+    /// from the castling pieces themselves, and the king may not castle out
+    /// of, through, or into check.
+    pub fn castling_moves(&self, color: Color, rights: &CastlingRights, moves: &mut MoveList) {
+        let pieces = self.pieces(color);
+        let rooks = pieces.piece_board(Piece::Rook);
+        let king_square = match pieces
+            .piece_board(Piece::King)
+            .board()
+            .occupied_squares()
+            .first()
+        {
+            Some(square) => *square,
+            None => return,
+        };
+        let occupied = self.occupied_squares();
+        let castles = CASTLE_SIDES.iter().filter_map(|side| {
+            let rook_square = rights.rook(color, *side)?;
+            if !rooks.occupied(&rook_square) {
+                return None;
+            }
+            let king_destination = side.king_destination(&king_square);
+            let rook_destination = side.rook_destination(&king_square);
+            let king_path = between(&king_square, &king_destination)
+                .set(&king_square)
+                .set(&king_destination);
+            let rook_path = between(&rook_square, &rook_destination).set(&rook_destination);
+            let others = occupied.unset(&king_square).unset(&rook_square);
+            if king_path.union(&rook_path).intersects(&others)
+                || self
+                    .attacked_squares(color.opponent(), &others)
+                    .intersects(&king_path)
+            {
+                return None;
+            }
+            Some(Move::castle(&king_square, &rook_square, *side))
+        });
+        moves.extend(castles);
+    }
+
+    /// Add the legal en passant captures onto the given target square to a
+    /// move list
+    ///
+    /// En passant removes two pawns from their squares at once, possibly
+    /// exposing the king along the rank, so the king's safety is checked
+    /// against the occupancy after the capture.
+    pub fn en_passant_moves(&self, color: Color, target: &Square, moves: &mut MoveList) {
+        let pawns = self.pieces(color).piece_board(Piece::Pawn).board();
+        let king = self.pieces(color).piece_board(Piece::King).board();
+        let occupied = self.occupied_squares();
+        let captures = pawn_attacks(target, color.opponent())
+            .intersection(&pawns)
+            .occupied_squares()
+            .into_iter()
+            .map(|from| Move::en_passant(&from, target))
+            .filter(|mv| {
+                let captured = mv.captured_square();
+                if !self
+                    .pieces(color.opponent())
+                    .piece_board(Piece::Pawn)
+                    .occupied(&captured)
+                {
+                    return false;
+                }
+                let occupied = occupied.unset(&mv.from()).unset(&captured).set(target);
+                king.occupied_squares().iter().all(|king_square| {
+                    self.attackers(king_square, color.opponent(), &occupied)
+                        .unset(&captured)
+                        .is_empty()
+                })
+            });
+        moves.extend(captures);
+    }
+
+    /// The square of the given side's king, if it has one
+    fn king_square(&self, color: Color) -> Option<Square> {
+        self.pieces(color)
+            .piece_board(Piece::King)
+            .board()
+            .occupied_squares()
+            .first()
+            .copied()
+    }
+
+    /// Every square occupied by a piece of either side
+    fn occupied_squares(&self) -> BitBoard {
+        self.pieces(Color::White)
+            .all()
+            .union(&self.pieces(Color::Black).all())
+    }
+
+    /// The pieces of the given side attacking a square
+    fn attackers(&self, square: &Square, by: Color, occupied: &BitBoard) -> BitBoard {
+        let pieces = self.pieces(by);
+        let board = |piece| pieces.piece_board(piece).board();
+        let rooks = board(Piece::Rook).union(&board(Piece::Queen));
+        let bishops = board(Piece::Bishop).union(&board(Piece::Queen));
+        rook_attacks(square, occupied)
+            .intersection(&rooks)
+            .union(&bishop_attacks(square, occupied).intersection(&bishops))
+            .union(&knight_attacks(square).intersection(&board(Piece::Knight)))
+            .union(&king_attacks(square).intersection(&board(Piece::King)))
+            // A pawn attacks our square if our square attacks it as an
+            // opposing pawn would
+            .union(&pawn_attacks(square, by.opponent()).intersection(&board(Piece::Pawn)))
+    }
+
+    /// Every square attacked by the given side
+    fn attacked_squares(&self, by: Color, occupied: &BitBoard) -> BitBoard {
+        self.pieces(by)
+            .iter_pieces()
+            .flat_map(|pieces| {
+                pieces
+                    .board()
+                    .occupied_squares()
+                    .into_iter()
+                    .map(move |from| (pieces.piece(), from))
+            })
+            .fold(NO_SQUARES, |attacked, (piece, from)| {
+                let attacks = match piece {
+                    Piece::Pawn => pawn_attacks(&from, by),
+                    _ => piece_targets(piece, by, &from, occupied, &NO_SQUARES),
+                };
+                attacked.union(&attacks)
+            })
+    }
+
+    /// Pieces of the given side pinned against their king, along with the
+    /// ray each one is confined to
+    fn pins(&self, color: Color, king_square: &Square) -> Vec<(Square, BitBoard)> {
+        let own = self.pieces(color).all();
+        let enemies = self.pieces(color.opponent());
+        let board = |piece| enemies.piece_board(piece).board();
+        let enemy = enemies.all();
+        // Enemy sliders which would attack the king if our pieces weren't
+        // in the way
+        let pinners = rook_attacks(king_square, &enemy)
+            .intersection(&board(Piece::Rook).union(&board(Piece::Queen)))
+            .union(
+                &bishop_attacks(king_square, &enemy)
+                    .intersection(&board(Piece::Bishop).union(&board(Piece::Queen))),
+            );
+        pinners
+            .occupied_squares()
+            .into_iter()
+            .filter_map(|pinner| {
+                let ray = between(king_square, &pinner);
+                let blockers = ray.intersection(&own);
+                if blockers.count() == 1 {
+                    Some((blockers.occupied_squares()[0], ray.set(&pinner)))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Generate moves for every piece of a side, within some restrictions
+    fn generate_moves(&self, color: Color, restrictions: &Restrictions) -> MoveList {
+        let own = self.pieces(color).all();
+        let enemy = self.pieces(color.opponent()).all();
+        let occupied = own.union(&enemy);
+        let mut moves = MoveList::new();
+        for pieces in self.pieces(color).iter_pieces() {
+            for from in pieces.board().occupied_squares() {
+                // Anything but our own pieces
+                let targets = own
+                    .relative_complement(&piece_targets(
+                        pieces.piece(),
+                        color,
+                        &from,
+                        &occupied,
+                        &enemy,
+                    ))
+                    .intersection(&restrictions.for_square(pieces.piece(), &from));
+                for to in targets.occupied_squares() {
+                    let is_pawn = pieces.piece() == Piece::Pawn;
+                    let mv = if enemy.occupied(&to) {
+                        Move::capture(&from, &to)
+                    } else if is_pawn && (to as i8 - from as i8).abs() == 16 {
+                        Move::double_push(&from, &to)
+                    } else {
+                        Move::new(&from, &to)
+                    };
+                    if is_pawn && PROMOTION_RANKS.occupied(&to) {
+                        moves.extend(
+                            PROMOTION_PIECES
+                                .iter()
+                                .map(|piece| mv.with_promotion(*piece)),
+                        );
+                    } else {
+                        moves.push(mv);
+                    }
+                }
+            }
+        }
+        moves
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::board::Pieces;
+    use crate::castling::CastleSide;
+
+    /// Build a board from lists of white and black piece squares
+    fn board(white: [&[Square]; 6], black: [&[Square]; 6]) -> Board {
+        let pieces = |squares: [&[Square]; 6]| {
+            Pieces::new(
+                BitBoard::from_squares(&squares[0].to_vec()),
+                BitBoard::from_squares(&squares[1].to_vec()),
+                BitBoard::from_squares(&squares[2].to_vec()),
+                BitBoard::from_squares(&squares[3].to_vec()),
+                BitBoard::from_squares(&squares[4].to_vec()),
+                BitBoard::from_squares(&squares[5].to_vec()),
+            )
+        };
+        Board::new(pieces(white), pieces(black))
+    }
+
+    /// Collect the destination squares of all moves from a square
+    fn targets(moves: &[Move], from: Square) -> BitBoard {
+        BitBoard::from_squares(
+            &moves
+                .iter()
+                .filter(|mv| mv.from() == from)
+                .map(|mv| mv.to())
+                .collect::<Vec<Square>>(),
+        )
+    }
+
+    #[test]
+    fn test_fresh_game_white() {
+        let moves = Board::fresh_game().pseudo_legal_moves(Color::White);
+        assert!(moves.len() == 20);
+        assert!(
+            targets(&moves, Square::E2) == BitBoard::from_squares(&vec![Square::E3, Square::E4])
+        );
+        assert!(
+            targets(&moves, Square::G1) == BitBoard::from_squares(&vec![Square::F3, Square::H3])
+        );
+        assert!(targets(&moves, Square::E1).is_empty());
+    }
+
+    #[test]
+    fn test_fresh_game_black() {
+        let moves = Board::fresh_game().pseudo_legal_moves(Color::Black);
+        assert!(moves.len() == 20);
+        assert!(
+            targets(&moves, Square::D7) == BitBoard::from_squares(&vec![Square::D6, Square::D5])
+        );
+        assert!(
+            targets(&moves, Square::B8) == BitBoard::from_squares(&vec![Square::A6, Square::C6])
+        );
+    }
+
+    #[test]
+    fn test_knight_in_corner() {
+        let board = board(
+            [&[Square::E1], &[], &[], &[], &[Square::A1], &[]],
+            [&[Square::E8], &[], &[], &[], &[], &[]],
+        );
+        let moves = board.pseudo_legal_moves(Color::White);
+        assert!(
+            targets(&moves, Square::A1) == BitBoard::from_squares(&vec![Square::B3, Square::C2])
+        );
+    }
+
+    #[test]
+    fn test_king_in_center() {
+        let board = board(
+            [&[Square::D4], &[], &[], &[], &[], &[Square::D5]],
+            [&[Square::E8], &[], &[], &[], &[], &[Square::E5]],
+        );
+        let moves = board.pseudo_legal_moves(Color::White);
+        assert!(
+            targets(&moves, Square::D4)
+                == BitBoard::from_squares(&vec![
+                    Square::C3,
+                    Square::D3,
+                    Square::E3,
+                    Square::C4,
+                    Square::E4,
+                    Square::C5,
+                    Square::E5,
+                ])
+        );
+    }
+
+    #[test]
+    fn test_rook_stops_at_blockers() {
+        let board = board(
+            [&[Square::H1], &[], &[Square::A1], &[], &[], &[Square::A4]],
+            [&[Square::H8], &[], &[], &[], &[Square::D1], &[]],
+        );
+        let moves = board.pseudo_legal_moves(Color::White);
+        assert!(
+            targets(&moves, Square::A1)
+                == BitBoard::from_squares(&vec![
+                    Square::A2,
+                    Square::A3,
+                    Square::B1,
+                    Square::C1,
+                    Square::D1,
+                ])
+        );
+    }
+
+    #[test]
+    fn test_bishop_does_not_wrap() {
+        let board = board(
+            [&[Square::A1], &[], &[], &[Square::H3], &[], &[]],
+            [&[Square::A8], &[], &[], &[], &[], &[Square::F5]],
+        );
+        let moves = board.pseudo_legal_moves(Color::White);
+        assert!(
+            targets(&moves, Square::H3)
+                == BitBoard::from_squares(&vec![Square::G2, Square::F1, Square::G4, Square::F5,])
+        );
+    }
+
+    #[test]
+    fn test_queen_combines_rook_and_bishop() {
+        let board = board(
+            [
+                &[Square::H1],
+                &[Square::A1],
+                &[],
+                &[],
+                &[],
+                &[Square::A2, Square::B2],
+            ],
+            [&[Square::H8], &[], &[], &[], &[], &[Square::B1]],
+        );
+        let moves = board.pseudo_legal_moves(Color::White);
+        assert!(targets(&moves, Square::A1) == BitBoard::from_squares(&vec![Square::B1]));
+    }
+
+    #[test]
+    fn test_pawn_pushes_and_captures() {
+        let board = board(
+            [&[Square::E1], &[], &[], &[], &[], &[Square::D2, Square::G2]],
+            [&[Square::E8], &[], &[], &[], &[Square::C3, Square::G4], &[]],
+        );
+        let moves = board.pseudo_legal_moves(Color::White);
+        assert!(
+            targets(&moves, Square::D2)
+                == BitBoard::from_squares(&vec![Square::D3, Square::D4, Square::C3])
+        );
+        assert!(targets(&moves, Square::G2) == BitBoard::from_squares(&vec![Square::G3]));
+    }
+
+    #[test]
+    fn test_blocked_pawn_cannot_push() {
+        let board = board(
+            [&[Square::E1], &[], &[], &[], &[], &[Square::D2]],
+            [&[Square::E8], &[], &[], &[], &[Square::D3], &[Square::A7]],
+        );
+        assert!(targets(&board.pseudo_legal_moves(Color::White), Square::D2).is_empty());
+        assert!(
+            targets(&board.pseudo_legal_moves(Color::Black), Square::A7)
+                == BitBoard::from_squares(&vec![Square::A6, Square::A5])
+        );
+    }
+
+    #[test]
+    fn test_in_check() {
+        let board = board(
+            [&[Square::E1], &[], &[], &[], &[], &[Square::D2]],
+            [&[Square::E8], &[], &[Square::E5], &[Square::B4], &[], &[]],
+        );
+        assert!(board.in_check(Color::White));
+        assert!(!board.in_check(Color::Black));
+        assert!(!Board::fresh_game().in_check(Color::White));
+    }
+
+    #[test]
+    fn test_attack_queries() {
+        let board = board(
+            [&[Square::E1], &[], &[], &[Square::D2], &[Square::E2], &[]],
+            [&[Square::E8], &[Square::A5], &[Square::E6], &[], &[], &[]],
+        );
+        assert!(board.is_square_attacked(&Square::E3, Color::Black));
+        assert!(board.is_square_attacked(&Square::E3, Color::White));
+        assert!(!board.is_square_attacked(&Square::H8, Color::White));
+        assert!(
+            board.attackers_to(&Square::E3)
+                == BitBoard::from_squares(&vec![Square::D2, Square::E6])
+        );
+        assert!(
+            board.attackers_to(&Square::C3)
+                == BitBoard::from_squares(&vec![Square::D2, Square::E2, Square::A5])
+        );
+        // Defenders count too
+        assert!(
+            board.attackers_to(&Square::D2)
+                == BitBoard::from_squares(&vec![Square::E1, Square::A5])
+        );
+        assert!(board.checkers(Color::White).is_empty());
+        assert!(
+            board.pinned(Color::White) == BitBoard::from_squares(&vec![Square::D2, Square::E2])
+        );
+        assert!(board.pinned(Color::Black).is_empty());
+    }
+
+    #[test]
+    fn test_checkers() {
+        let board = board(
+            [&[Square::E1], &[], &[], &[], &[], &[]],
+            [&[Square::E8], &[], &[Square::E5], &[], &[Square::D3], &[]],
+        );
+        assert!(board.in_check(Color::White));
+        assert!(
+            board.checkers(Color::White) == BitBoard::from_squares(&vec![Square::E5, Square::D3])
+        );
+        assert!(board.checkers(Color::Black).is_empty());
+        assert!(Board::fresh_game().checkers(Color::White).is_empty());
+        assert!(Board::fresh_game().pinned(Color::White).is_empty());
+    }
+
+    #[test]
+    fn test_legal_fresh_game() {
+        assert!(Board::fresh_game().legal_moves(Color::White).len() == 20);
+        assert!(Board::fresh_game().legal_moves(Color::Black).len() == 20);
+    }
+
+    #[test]
+    fn test_between() {
+        assert!(
+            between(&Square::A1, &Square::D4)
+                == BitBoard::from_squares(&vec![Square::B2, Square::C3])
+        );
+        assert!(
+            between(&Square::H1, &Square::H4)
+                == BitBoard::from_squares(&vec![Square::H2, Square::H3])
+        );
+        assert!(between(&Square::A1, &Square::B2).is_empty());
+        assert!(between(&Square::A1, &Square::B3).is_empty());
+    }
+
+    #[test]
+    fn test_pinned_piece_stays_on_pin_ray() {
+        let board = board(
+            [&[Square::E1], &[], &[Square::E2], &[], &[], &[]],
+            [&[Square::A8], &[], &[Square::E8], &[], &[], &[]],
+        );
+        let moves = board.legal_moves(Color::White);
+        assert!(
+            targets(&moves, Square::E2)
+                == BitBoard::from_squares(&vec![
+                    Square::E3,
+                    Square::E4,
+                    Square::E5,
+                    Square::E6,
+                    Square::E7,
+                    Square::E8,
+                ])
+        );
+        assert!(moves.len() == 10);
+    }
+
+    #[test]
+    fn test_pinned_piece_off_ray_cannot_move() {
+        let board = board(
+            [&[Square::E1], &[], &[], &[Square::E2], &[], &[]],
+            [&[Square::A8], &[], &[Square::E8], &[], &[], &[]],
+        );
+        assert!(targets(&board.legal_moves(Color::White), Square::E2).is_empty());
+    }
+
+    #[test]
+    fn test_check_evasions() {
+        let board = board(
+            [&[Square::E1], &[], &[Square::A6], &[Square::B5], &[], &[]],
+            [&[Square::H8], &[], &[Square::E8], &[], &[], &[]],
+        );
+        let moves = board.legal_moves(Color::White);
+        assert!(
+            targets(&moves, Square::E1)
+                == BitBoard::from_squares(&vec![Square::D1, Square::D2, Square::F1, Square::F2])
+        );
+        assert!(targets(&moves, Square::A6) == BitBoard::from_squares(&vec![Square::E6]));
+        assert!(
+            targets(&moves, Square::B5) == BitBoard::from_squares(&vec![Square::E2, Square::E8])
+        );
+        assert!(moves.len() == 7);
+    }
+
+    #[test]
+    fn test_double_check_only_king_moves() {
+        let board = board(
+            [&[Square::E1], &[], &[Square::A3], &[], &[], &[]],
+            [&[Square::H8], &[], &[Square::E8], &[], &[Square::D3], &[]],
+        );
+        let moves = board.legal_moves(Color::White);
+        assert!(
+            targets(&moves, Square::E1)
+                == BitBoard::from_squares(&vec![Square::D1, Square::D2, Square::F1])
+        );
+        assert!(moves.len() == 3);
+    }
+
+    #[test]
+    fn test_king_cannot_retreat_along_checking_ray() {
+        let board = board(
+            [&[Square::E4], &[], &[], &[], &[], &[]],
+            [&[Square::A8], &[], &[Square::E8], &[], &[], &[]],
+        );
+        assert!(
+            targets(&board.legal_moves(Color::White), Square::E4)
+                == BitBoard::from_squares(&vec![
+                    Square::D3,
+                    Square::D4,
+                    Square::D5,
+                    Square::F3,
+                    Square::F4,
+                    Square::F5,
+                ])
+        );
+    }
+
+    #[test]
+    fn test_king_cannot_capture_defended_piece() {
+        let board = board(
+            [&[Square::E1], &[], &[], &[], &[], &[]],
+            [
+                &[Square::E8],
+                &[],
+                &[],
+                &[],
+                &[Square::C3],
+                &[Square::E2, Square::D3],
+            ],
+        );
+        let moves = board.legal_moves(Color::White);
+        // e2 is defended by the d3 pawn and the knight, while d1 and f1 are
+        // covered by the e2 pawn
+        assert!(
+            targets(&moves, Square::E1) == BitBoard::from_squares(&vec![Square::D2, Square::F2])
+        );
+    }
+
+    /// Kings and rooks on their starting squares, plus some extra pieces
+    fn castling_board(white: [&[Square]; 4], black: [&[Square]; 4]) -> Board {
+        board(
+            [
+                &[Square::E1],
+                white[0],
+                &[Square::A1, Square::H1],
+                white[1],
+                white[2],
+                white[3],
+            ],
+            [
+                &[Square::E8],
+                black[0],
+                &[Square::A8, Square::H8],
+                black[1],
+                black[2],
+                black[3],
+            ],
+        )
+    }
+
+    fn castles(board: &Board, color: Color, rights: &CastlingRights) -> Vec<(Square, Square)> {
+        let mut moves = MoveList::new();
+        board.castling_moves(color, rights, &mut moves);
+        moves.iter().map(|mv| (mv.from(), mv.to())).collect()
+    }
+
+    #[test]
+    fn test_castling_both_wings() {
+        let board = castling_board([&[], &[], &[], &[]], [&[], &[], &[], &[]]);
+        assert!(
+            castles(&board, Color::White, &CastlingRights::all())
+                == vec![(Square::E1, Square::H1), (Square::E1, Square::A1)]
+        );
+        assert!(
+            castles(&board, Color::Black, &CastlingRights::all())
+                == vec![(Square::E8, Square::H8), (Square::E8, Square::A8)]
+        );
+    }
+
+    #[test]
+    fn test_castling_requires_rights() {
+        let board = castling_board([&[], &[], &[], &[]], [&[], &[], &[], &[]]);
+        let rights = CastlingRights::all().revoke(Color::White, CastleSide::King);
+        assert!(castles(&board, Color::White, &rights) == vec![(Square::E1, Square::A1)]);
+        assert!(castles(&board, Color::White, &CastlingRights::none()).is_empty());
+    }
+
+    #[test]
+    fn test_castling_path_must_be_empty() {
+        // Only the b1 square needs to be empty for the rook, not the king
+        let board = castling_board([&[], &[], &[Square::B1], &[]], [&[], &[], &[], &[]]);
+        assert!(
+            castles(&board, Color::White, &CastlingRights::all()) == vec![(Square::E1, Square::H1)]
+        );
+        let board = castling_board([&[], &[Square::F1], &[], &[]], [&[], &[], &[], &[]]);
+        assert!(
+            castles(&board, Color::White, &CastlingRights::all()) == vec![(Square::E1, Square::A1)]
+        );
+    }
+
+    #[test]
+    fn test_castling_through_attacked_square() {
+        let board = castling_board([&[], &[], &[], &[]], [&[], &[Square::A6], &[], &[]]);
+        // The bishop covers f1
+        assert!(
+            castles(&board, Color::White, &CastlingRights::all()) == vec![(Square::E1, Square::A1)]
+        );
+        let board = castling_board([&[], &[], &[], &[]], [&[], &[], &[Square::C3], &[]]);
+        // The knight covers d1
+        assert!(
+            castles(&board, Color::White, &CastlingRights::all()) == vec![(Square::E1, Square::H1)]
+        );
+    }
+
+    #[test]
+    fn test_no_castling_out_of_check() {
+        let board = castling_board([&[], &[], &[], &[]], [&[Square::E5], &[], &[], &[]]);
+        assert!(castles(&board, Color::White, &CastlingRights::all()).is_empty());
+    }
+
+    fn en_passants(board: &Board, color: Color, target: Square) -> Vec<(Square, Square)> {
+        let mut moves = MoveList::new();
+        board.en_passant_moves(color, &target, &mut moves);
+        moves.iter().map(|mv| (mv.from(), mv.to())).collect()
+    }
+
+    #[test]
+    fn test_en_passant_from_both_sides() {
+        let board = board(
+            [&[Square::E1], &[], &[], &[], &[], &[Square::C5, Square::E5]],
+            [&[Square::E8], &[], &[], &[], &[], &[Square::D5]],
+        );
+        assert!(
+            en_passants(&board, Color::White, Square::D6)
+                == vec![(Square::C5, Square::D6), (Square::E5, Square::D6)]
+        );
+    }
+
+    #[test]
+    fn test_en_passant_requires_captured_pawn() {
+        let board = board(
+            [&[Square::E1], &[], &[], &[], &[], &[Square::E5]],
+            [&[Square::E8], &[], &[], &[], &[Square::D5], &[]],
+        );
+        assert!(en_passants(&board, Color::White, Square::D6).is_empty());
+    }
+
+    #[test]
+    fn test_en_passant_exposing_king_on_rank() {
+        let board = board(
+            [&[Square::A5], &[], &[], &[], &[], &[Square::B5]],
+            [&[Square::E8], &[], &[Square::H5], &[], &[], &[Square::C5]],
+        );
+        assert!(en_passants(&board, Color::White, Square::C6).is_empty());
+    }
+
+    #[test]
+    fn test_en_passant_capturing_checker() {
+        // The pawn that just moved to d5 checks the king on e4
+        let board = board(
+            [&[Square::E4], &[], &[], &[], &[], &[Square::E5]],
+            [&[Square::E8], &[], &[], &[], &[], &[Square::D5]],
+        );
+        assert!(en_passants(&board, Color::White, Square::D6) == vec![(Square::E5, Square::D6)]);
+    }
+
+    #[test]
+    fn test_en_passant_must_resolve_check() {
+        let board = board(
+            [&[Square::E1], &[], &[], &[], &[], &[Square::E5]],
+            [&[Square::E8], &[], &[Square::A1], &[], &[], &[Square::D5]],
+        );
+        assert!(en_passants(&board, Color::White, Square::D6).is_empty());
+    }
+
+    #[test]
+    fn test_promotions() {
+        let board = board(
+            [&[Square::E1], &[], &[], &[], &[], &[Square::G7]],
+            [&[Square::A8], &[], &[], &[], &[Square::H8], &[Square::B2]],
+        );
+        let moves = board.legal_moves(Color::White);
+        let promotions = moves
+            .iter()
+            .filter(|mv| mv.from() == Square::G7)
+            .map(|mv| (mv.to(), mv.promotion()))
+            .collect::<Vec<_>>();
+        assert!(promotions.len() == 8);
+        for piece in &PROMOTION_PIECES {
+            assert!(promotions.contains(&(Square::G8, Some(*piece))));
+            assert!(promotions.contains(&(Square::H8, Some(*piece))));
+        }
+        let moves = board.legal_moves(Color::Black);
+        assert!(
+            moves
+                .iter()
+                .filter(|mv| mv.from() == Square::B2 && mv.promotion().is_some())
+                .count()
+                == 4
+        );
+    }
+}
@@ -0,0 +1,480 @@
+//! Compact move representation
+//!
+//! A move is packed into 16 bits: six bits each for the from and to square
+//! indices, and four bits of flags describing what sort of move it is.
+//!
+//! ```text
+//! flags | to   | from
+//! 15-12 | 11-6 | 5-0
+//! ```
+
+use std::error::Error;
+use std::fmt;
+use std::ops::Deref;
+use std::str::FromStr;
+
+use crate::bitboard::BitBoard;
+use crate::board::Piece;
+use crate::castling::CastleSide;
+use crate::square::{Square, SQUARES};
+
+// Flag values, following the usual from-to-flags layout
+const QUIET: u16 = 0b0000;
+const DOUBLE_PAWN_PUSH: u16 = 0b0001;
+const KING_CASTLE: u16 = 0b0010;
+const QUEEN_CASTLE: u16 = 0b0011;
+const CAPTURE: u16 = 0b0100;
+const EN_PASSANT: u16 = 0b0101;
+const PROMOTION: u16 = 0b1000;
+// The low two bits of a promotion select the piece
+const KNIGHT_PROMOTION: u16 = PROMOTION;
+const BISHOP_PROMOTION: u16 = PROMOTION | 0b01;
+const ROOK_PROMOTION: u16 = PROMOTION | 0b10;
+const QUEEN_PROMOTION: u16 = PROMOTION | 0b11;
+
+const SQUARE_MASK: u16 = 0b11_1111;
+const TO_SHIFT: u16 = 6;
+const FLAGS_SHIFT: u16 = 12;
+
+/// Moves which need more than relocating a single piece
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveKind {
+    Normal,
+    /// The king moves from the `from` square and the rook from the `to`
+    /// square, each landing on its castling destination.
+    Castle(CastleSide),
+    /// A pawn captures a pawn which just passed it with a double push. The
+    /// captured pawn stands beside the `from` square, not on the `to` square.
+    EnPassant,
+}
+
+/// A move packed into 16 bits
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Move {
+    bits: u16,
+}
+/// Constructors
+impl Move {
+    const fn with_flags(from: &Square, to: &Square, flags: u16) -> Self {
+        Self {
+            bits: (*from as u16) | ((*to as u16) << TO_SHIFT) | (flags << FLAGS_SHIFT),
+        }
+    }
+    /// A quiet move, which captures nothing
+    pub const fn new(from: &Square, to: &Square) -> Self {
+        Self::with_flags(from, to, QUIET)
+    }
+    /// A move capturing the piece on the `to` square
+    pub const fn capture(from: &Square, to: &Square) -> Self {
+        Self::with_flags(from, to, CAPTURE)
+    }
+    /// A pawn moving two squares forward from its starting rank
+    pub const fn double_push(from: &Square, to: &Square) -> Self {
+        Self::with_flags(from, to, DOUBLE_PAWN_PUSH)
+    }
+    /// Construct a castling move from the king's and the rook's squares
+    pub const fn castle(king: &Square, rook: &Square, side: CastleSide) -> Self {
+        let flags = match side {
+            CastleSide::King => KING_CASTLE,
+            CastleSide::Queen => QUEEN_CASTLE,
+        };
+        Self::with_flags(king, rook, flags)
+    }
+    /// Construct an en passant capture
+    pub const fn en_passant(from: &Square, to: &Square) -> Self {
+        Self::with_flags(from, to, EN_PASSANT)
+    }
+    /// Return the same move, promoting the moving pawn to the given piece
+    ///
+    /// Only queens, rooks, bishops and knights can be promoted to, and
+    /// there's no encoding for a king or pawn. Asking for either panics.
+    pub const fn with_promotion(self, piece: Piece) -> Self {
+        let promotion = match piece {
+            Piece::Queen => QUEEN_PROMOTION,
+            Piece::Rook => ROOK_PROMOTION,
+            Piece::Bishop => BISHOP_PROMOTION,
+            Piece::Knight => KNIGHT_PROMOTION,
+            Piece::King | Piece::Pawn => panic!("a pawn can't promote to a king or pawn"),
+        };
+        let capture = self.flags() & CAPTURE;
+        Self {
+            bits: (self.bits & !(0b1111 << FLAGS_SHIFT)) | ((promotion | capture) << FLAGS_SHIFT),
+        }
+    }
+}
+/// Accessors
+impl Move {
+    const fn flags(&self) -> u16 {
+        self.bits >> FLAGS_SHIFT
+    }
+    /// The raw 16-bit encoding of the move
+    pub const fn bits(&self) -> u16 {
+        self.bits
+    }
+    /// The square the piece is moving from
+    pub const fn from(&self) -> Square {
+        SQUARES[(self.bits & SQUARE_MASK) as usize]
+    }
+    /// The square the piece is moving to
+    pub const fn to(&self) -> Square {
+        SQUARES[((self.bits >> TO_SHIFT) & SQUARE_MASK) as usize]
+    }
+    /// The from square as a bitboard
+    pub const fn origin_board(&self) -> BitBoard {
+        BitBoard::from_square(&self.from())
+    }
+    /// The to square as a bitboard
+    pub const fn target_board(&self) -> BitBoard {
+        BitBoard::from_square(&self.to())
+    }
+    /// What sort of move this is
+    pub const fn kind(&self) -> MoveKind {
+        match self.flags() {
+            KING_CASTLE => MoveKind::Castle(CastleSide::King),
+            QUEEN_CASTLE => MoveKind::Castle(CastleSide::Queen),
+            EN_PASSANT => MoveKind::EnPassant,
+            _ => MoveKind::Normal,
+        }
+    }
+    /// The piece a pawn is promoted to, if any
+    pub const fn promotion(&self) -> Option<Piece> {
+        if self.flags() & PROMOTION == 0 {
+            return None;
+        }
+        match self.flags() & !CAPTURE {
+            KNIGHT_PROMOTION => Some(Piece::Knight),
+            BISHOP_PROMOTION => Some(Piece::Bishop),
+            ROOK_PROMOTION => Some(Piece::Rook),
+            _ => Some(Piece::Queen),
+        }
+    }
+    /// Whether the move captures a piece, including en passant
+    pub const fn is_capture(&self) -> bool {
+        self.flags() & CAPTURE != 0
+    }
+    /// Whether the move is a pawn's double push
+    pub const fn is_double_push(&self) -> bool {
+        self.flags() == DOUBLE_PAWN_PUSH
+    }
+    /// Whether the move is an en passant capture
+    pub const fn is_en_passant(&self) -> bool {
+        self.flags() == EN_PASSANT
+    }
+    /// Whether the move is castling to either wing
+    pub const fn is_castle(&self) -> bool {
+        matches!(self.flags(), KING_CASTLE | QUEEN_CASTLE)
+    }
+    /// The square holding any piece captured by this move
+    ///
+    /// This is the `to` square for everything but en passant, where it's
+    /// the square on the `from` rank and the `to` file.
+    pub const fn captured_square(&self) -> Square {
+        let from = (self.bits & SQUARE_MASK) as usize;
+        let to = ((self.bits >> TO_SHIFT) & SQUARE_MASK) as usize;
+        match self.flags() {
+            EN_PASSANT => SQUARES[(from & !7) | (to & 7)],
+            _ => SQUARES[to],
+        }
+    }
+}
+/// Long algebraic (UCI) notation
+impl Move {
+    /// The move in UCI long algebraic notation, like `e2e4` or `e7e8q`
+    ///
+    /// Castling is written as the king's move in standard chess (`e1g1`),
+    /// or as the king taking its own rook for Chess960 (`e1h1`), which
+    /// can't be confused with an ordinary king move.
+    pub fn to_uci(self, chess960: bool) -> String {
+        let to = match self.kind() {
+            MoveKind::Castle(side) if !chess960 => side.king_destination(&self.from()),
+            _ => self.to(),
+        };
+        let mut uci = format!("{}{}", self.from(), to).to_lowercase();
+        if let Some(piece) = self.promotion() {
+            uci.push(piece.letter().to_ascii_lowercase());
+        }
+        uci
+    }
+}
+/// Moves display in UCI long algebraic notation, with standard castling
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_uci(false))
+    }
+}
+/// Read a move in UCI long algebraic notation
+///
+/// Whether a move captures or castles depends on the position, so this only
+/// gives the squares and any promotion, as a quiet move. Use
+/// `GameState::parse_uci` to find the legal move it refers to.
+impl FromStr for Move {
+    type Err = UciError;
+    fn from_str(uci: &str) -> Result<Self, Self::Err> {
+        let invalid = || UciError::Invalid(uci.to_string());
+        let square = |name: &str| match name.parse::<Square>() {
+            Ok(square) if name == name.to_ascii_lowercase() => Ok(square),
+            _ => Err(invalid()),
+        };
+        let (from, to, promotion) = match uci.len() {
+            4 | 5 if uci.is_char_boundary(2) && uci.is_char_boundary(4) => (
+                square(&uci[..2])?,
+                square(&uci[2..4])?,
+                uci[4..].chars().next(),
+            ),
+            _ => return Err(invalid()),
+        };
+        let mv = Self::new(&from, &to);
+        match promotion {
+            None => Ok(mv),
+            Some(letter) => match Piece::from_letter(letter.to_ascii_uppercase()) {
+                Some(piece @ (Piece::Queen | Piece::Rook | Piece::Bishop | Piece::Knight))
+                    if letter.is_ascii_lowercase() =>
+                {
+                    Ok(mv.with_promotion(piece))
+                }
+                _ => Err(invalid()),
+            },
+        }
+    }
+}
+
+/// Why a UCI move couldn't be read
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UciError {
+    /// The text isn't a move in long algebraic notation
+    Invalid(String),
+    /// No legal move in the position matches
+    Illegal(String),
+}
+impl fmt::Display for UciError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Invalid(uci) => write!(f, "invalid UCI move {:?}", uci),
+            Self::Illegal(uci) => write!(f, "illegal move {:?}", uci),
+        }
+    }
+}
+impl Error for UciError {}
+
+impl fmt::Debug for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Move({} -> {}", self.from(), self.to())?;
+        if let Some(piece) = self.promotion() {
+            write!(f, " ={:?}", piece)?;
+        }
+        if self.is_capture() {
+            write!(f, ", capture")?;
+        }
+        match self.kind() {
+            MoveKind::Castle(side) => write!(f, ", castle {:?})", side),
+            MoveKind::EnPassant => write!(f, ", en passant)"),
+            MoveKind::Normal => write!(f, ")"),
+        }
+    }
+}
+
+/// More than the greatest number of moves in any position with material
+/// reachable from the starting set
+///
+/// A side has at most its starting pieces plus eight more from promotions
+/// or pawns. A king, nine queens and two each of rooks, bishops and
+/// knights have at most 10 + 9 × 27 + 2 × 14 + 2 × 13 + 2 × 8 = 323
+/// moves, counting both castles, and a pawn never has more than a queen.
+pub const MAX_MOVES: usize = 324;
+
+/// A fixed-capacity list of moves, kept on the stack during generation
+#[derive(Clone, Copy)]
+pub struct MoveList {
+    moves: [Move; MAX_MOVES],
+    len: usize,
+    overflowed: bool,
+}
+impl MoveList {
+    pub const fn new() -> Self {
+        Self {
+            moves: [Move { bits: 0 }; MAX_MOVES],
+            len: 0,
+            overflowed: false,
+        }
+    }
+    /// Add a move to the end of the list
+    ///
+    /// Moves pushed once the list is full are dropped, and `overflowed`
+    /// reports it. No position with material reachable from the starting
+    /// set fills the list, but a board built piece by piece might.
+    pub fn push(&mut self, mv: Move) {
+        match self.moves.get_mut(self.len) {
+            Some(slot) => {
+                *slot = mv;
+                self.len += 1;
+            }
+            None => self.overflowed = true,
+        }
+    }
+    /// Whether any moves were dropped because the list was full
+    pub const fn overflowed(&self) -> bool {
+        self.overflowed
+    }
+}
+impl Default for MoveList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+/// A move list behaves as a slice of the moves pushed so far
+impl Deref for MoveList {
+    type Target = [Move];
+    fn deref(&self) -> &[Move] {
+        &self.moves[..self.len]
+    }
+}
+impl Extend<Move> for MoveList {
+    fn extend<T: IntoIterator<Item = Move>>(&mut self, iter: T) {
+        iter.into_iter().for_each(|mv| self.push(mv));
+    }
+}
+impl IntoIterator for MoveList {
+    type Item = Move;
+    type IntoIter = std::iter::Take<std::array::IntoIter<Move, MAX_MOVES>>;
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIterator::into_iter(self.moves).take(self.len)
+    }
+}
+impl<'a> IntoIterator for &'a MoveList {
+    type Item = &'a Move;
+    type IntoIter = std::slice::Iter<'a, Move>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+impl fmt::Debug for MoveList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_size() {
+        assert!(std::mem::size_of::<Move>() == 2);
+    }
+
+    #[test]
+    fn test_squares_round_trip() {
+        for from in SQUARES.iter() {
+            for to in SQUARES.iter() {
+                let mv = Move::new(from, to);
+                assert!(mv.from() == *from);
+                assert!(mv.to() == *to);
+                assert!(mv.kind() == MoveKind::Normal);
+                assert!(!mv.is_capture());
+            }
+        }
+    }
+
+    #[test]
+    fn test_boards() {
+        let mv = Move::new(&Square::E2, &Square::E4);
+        assert!(mv.origin_board() == BitBoard::from_square(&Square::E2));
+        assert!(mv.target_board() == BitBoard::from_square(&Square::E4));
+    }
+
+    #[test]
+    fn test_flags() {
+        assert!(Move::capture(&Square::A1, &Square::A8).is_capture());
+        assert!(Move::double_push(&Square::E7, &Square::E5).is_double_push());
+        let castle = Move::castle(&Square::E1, &Square::A1, CastleSide::Queen);
+        assert!(castle.is_castle());
+        assert!(castle.kind() == MoveKind::Castle(CastleSide::Queen));
+        assert!(!castle.is_capture());
+        let en_passant = Move::en_passant(&Square::E5, &Square::D6);
+        assert!(en_passant.is_en_passant());
+        assert!(en_passant.is_capture());
+        assert!(en_passant.captured_square() == Square::D5);
+    }
+
+    #[test]
+    fn test_promotions() {
+        for piece in &[Piece::Queen, Piece::Rook, Piece::Bishop, Piece::Knight] {
+            let quiet = Move::new(&Square::B7, &Square::B8).with_promotion(*piece);
+            assert!(quiet.promotion() == Some(*piece));
+            assert!(!quiet.is_capture());
+            let capture = Move::capture(&Square::B7, &Square::A8).with_promotion(*piece);
+            assert!(capture.promotion() == Some(*piece));
+            assert!(capture.is_capture());
+            assert!(capture.to() == Square::A8);
+        }
+        assert!(Move::new(&Square::B6, &Square::B7).promotion().is_none());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_promotion_to_king() {
+        Move::new(&Square::B7, &Square::B8).with_promotion(Piece::King);
+    }
+
+    #[test]
+    fn test_uci() {
+        assert!(Move::double_push(&Square::E2, &Square::E4).to_string() == "e2e4");
+        let promotion = Move::capture(&Square::B7, &Square::A8).with_promotion(Piece::Knight);
+        assert!(promotion.to_string() == "b7a8n");
+        let king = Move::castle(&Square::E1, &Square::H1, CastleSide::King);
+        assert!(king.to_string() == "e1g1");
+        assert!(king.to_uci(true) == "e1h1");
+        let queen = Move::castle(&Square::E8, &Square::A8, CastleSide::Queen);
+        assert!(queen.to_uci(false) == "e8c8");
+        assert!(queen.to_uci(true) == "e8a8");
+    }
+
+    #[test]
+    fn test_parse_uci() {
+        assert!("e2e4".parse::<Move>() == Ok(Move::new(&Square::E2, &Square::E4)));
+        assert!(
+            "e7e8q".parse::<Move>()
+                == Ok(Move::new(&Square::E7, &Square::E8).with_promotion(Piece::Queen))
+        );
+        for invalid in [
+            "", "e2", "e2e9", "i2i4", "e2e4 ", "e7e8k", "e7e8Q", "e7e8qq",
+        ]
+        .iter()
+        {
+            assert!(invalid.parse::<Move>() == Err(UciError::Invalid(invalid.to_string())));
+        }
+    }
+
+    #[test]
+    fn test_move_list() {
+        let mut moves = MoveList::new();
+        assert!(moves.is_empty());
+        moves.push(Move::new(&Square::E2, &Square::E4));
+        moves.extend(vec![Move::new(&Square::D2, &Square::D4)]);
+        assert!(moves.len() == 2);
+        assert!(moves.contains(&Move::new(&Square::D2, &Square::D4)));
+        assert!(moves[0] == Move::new(&Square::E2, &Square::E4));
+        assert!(moves.into_iter().count() == 2);
+    }
+
+    #[test]
+    fn test_move_list_capacity() {
+        // The most legal moves known in any position
+        let state = crate::game_state::GameState::from_fen(
+            "R6R/3Q4/1Q4Q1/4Q3/2Q4Q/Q4Q2/pp1Q4/kBNN1KB1 w - - 0 1",
+        )
+        .unwrap();
+        assert!(state.legal_moves().len() == 218);
+        assert!(state.pseudo_legal_moves().len() <= MAX_MOVES);
+        assert!(!state.pseudo_legal_moves().overflowed());
+
+        // Anything past the end is dropped rather than panicking
+        let mut moves = MoveList::new();
+        let mv = Move::new(&Square::E2, &Square::E4);
+        for _ in 0..=MAX_MOVES {
+            moves.push(mv);
+        }
+        assert!(moves.len() == MAX_MOVES);
+        assert!(moves.overflowed());
+    }
+}
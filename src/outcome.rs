@@ -0,0 +1,234 @@
+//! How games end
+//!
+//! Checkmate and stalemate end a game at once, as do the draws the FIDE laws
+//! apply without a claim: a position repeated five times, seventy-five moves
+//! by each side without a capture or pawn move, and a dead position where
+//! neither side has the material left to mate. Threefold repetition and the
+//! fifty-move rule only give a player the right to claim a draw, so they're
+//! reported separately.
+
+use crate::board::Color;
+use crate::game_state::GameState;
+use crate::pgn::PgnResult;
+
+/// The score of a finished game
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
+}
+impl GameResult {
+    /// The win for the given side
+    pub const fn win(winner: Color) -> Self {
+        match winner {
+            Color::White => Self::WhiteWins,
+            Color::Black => Self::BlackWins,
+        }
+    }
+}
+impl From<GameResult> for PgnResult {
+    fn from(result: GameResult) -> Self {
+        match result {
+            GameResult::WhiteWins => Self::WhiteWins,
+            GameResult::BlackWins => Self::BlackWins,
+            GameResult::Draw => Self::Draw,
+        }
+    }
+}
+
+/// The reason a game is over, or may be declared drawn
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Checkmate {
+        winner: Color,
+    },
+    Stalemate,
+    /// Neither side has enough material left to checkmate
+    InsufficientMaterial,
+    /// The same position has occurred five times
+    FivefoldRepetition,
+    /// Seventy-five moves by each side without a capture or pawn move
+    SeventyFiveMoves,
+    /// The same position has occurred three times, which either side may
+    /// claim as a draw
+    ThreefoldRepetition,
+    /// Fifty moves by each side without a capture or pawn move, which either
+    /// side may claim as a draw
+    FiftyMoves,
+}
+impl Outcome {
+    pub const fn result(self) -> GameResult {
+        match self {
+            Self::Checkmate { winner } => GameResult::win(winner),
+            _ => GameResult::Draw,
+        }
+    }
+    /// Whether the game ends without either player having to claim it
+    pub const fn is_automatic(self) -> bool {
+        !matches!(self, Self::ThreefoldRepetition | Self::FiftyMoves)
+    }
+}
+
+impl GameState {
+    /// How the game has ended, if it has
+    ///
+    /// Only endings which apply without a claim are given; see
+    /// `claimable_draw` for the rest. Checkmate takes precedence over the
+    /// seventy-five move rule.
+    pub fn outcome(&self) -> Option<Outcome> {
+        if self.legal_moves().is_empty() {
+            return Some(if self.in_check() {
+                Outcome::Checkmate {
+                    winner: self.turn().opponent(),
+                }
+            } else {
+                Outcome::Stalemate
+            });
+        }
+        if self.board().material().is_dead() {
+            Some(Outcome::InsufficientMaterial)
+        } else if self.repetitions() >= 5 {
+            Some(Outcome::FivefoldRepetition)
+        } else if self.meta().halfmove_clock() >= 150 {
+            Some(Outcome::SeventyFiveMoves)
+        } else {
+            None
+        }
+    }
+    /// A draw either side could claim now, if the game isn't already over
+    pub fn claimable_draw(&self) -> Option<Outcome> {
+        if self.outcome().is_some() {
+            None
+        } else if self.repetitions() >= 3 {
+            Some(Outcome::ThreefoldRepetition)
+        } else if self.meta().halfmove_clock() >= 100 {
+            Some(Outcome::FiftyMoves)
+        } else {
+            None
+        }
+    }
+    /// The result of the game, if it has ended without a claim
+    pub fn result(&self) -> Option<GameResult> {
+        self.outcome().map(Outcome::result)
+    }
+    /// How many times the current position has occurred, counting this one
+    ///
+    /// Positions count as the same when they have the same pieces, side to
+    /// move, castling rights and usable en passant captures, which is just
+    /// what their Zobrist keys cover. Only positions since the last capture
+    /// or pawn move can repeat, and only moves in the history are counted.
+    pub fn repetitions(&self) -> usize {
+        1 + self
+            .history()
+            .iter()
+            .rev()
+            .take(self.meta().halfmove_clock() as usize)
+            .filter(|undo| undo.zobrist() == self.zobrist())
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn state(fen: &str) -> GameState {
+        GameState::from_fen(fen).unwrap()
+    }
+
+    #[test]
+    fn test_checkmate_and_stalemate() {
+        let mut fools_mate = GameState::fresh_game();
+        fools_mate.play(&["f3", "e5", "g4", "Qh4#"]);
+        let outcome = fools_mate.outcome().unwrap();
+        assert!(
+            outcome
+                == Outcome::Checkmate {
+                    winner: Color::Black
+                }
+        );
+        assert!(outcome.is_automatic());
+        assert!(fools_mate.result() == Some(GameResult::BlackWins));
+        assert!(PgnResult::from(GameResult::BlackWins) == PgnResult::BlackWins);
+
+        let stalemate = state("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1");
+        assert!(stalemate.outcome() == Some(Outcome::Stalemate));
+        assert!(stalemate.result() == Some(GameResult::Draw));
+        assert!(GameState::fresh_game().outcome().is_none());
+        assert!(GameState::fresh_game().result().is_none());
+    }
+
+    #[test]
+    fn test_insufficient_material() {
+        let dead = [
+            "8/8/4k3/8/8/3K4/8/8 w - - 0 1",
+            "8/8/4k3/8/8/3KB3/8/8 w - - 0 1",
+            "8/8/4kn2/8/8/3K4/8/8 w - - 0 1",
+            // Bishops of both sides, all on light squares
+            "8/8/4k3/5b2/8/3K4/2B1B3/8 w - - 0 1",
+        ];
+        for fen in dead.iter() {
+            assert!(
+                state(fen).outcome() == Some(Outcome::InsufficientMaterial),
+                "{}",
+                fen
+            );
+        }
+        assert!(state(dead[0]).result() == Some(GameResult::Draw));
+        let alive = [
+            "8/8/4k3/8/8/3KP3/8/8 w - - 0 1",
+            "8/8/4k3/8/8/3K3R/8/8 w - - 0 1",
+            "8/8/4k3/8/8/3KNN2/8/8 w - - 0 1",
+            "8/8/4kn2/8/8/3KB3/8/8 w - - 0 1",
+            // Bishops on opposite colours
+            "8/8/4k3/5b2/8/3K4/8/4B3 w - - 0 1",
+        ];
+        for fen in alive.iter() {
+            assert!(state(fen).outcome().is_none(), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn test_repetition() {
+        let mut state = GameState::fresh_game();
+        let shuffle = ["Nf3", "Nf6", "Ng1", "Ng8"];
+        assert!(state.repetitions() == 1);
+        state.play(&shuffle);
+        assert!(state.repetitions() == 2);
+        assert!(state.claimable_draw().is_none());
+        state.play(&shuffle);
+        assert!(state.repetitions() == 3);
+        assert!(state.claimable_draw() == Some(Outcome::ThreefoldRepetition));
+        assert!(!Outcome::ThreefoldRepetition.is_automatic());
+        assert!(state.outcome().is_none());
+        state.play(&shuffle);
+        state.play(&shuffle);
+        assert!(state.repetitions() == 5);
+        assert!(state.outcome() == Some(Outcome::FivefoldRepetition));
+        assert!(state.claimable_draw().is_none());
+
+        // A pawn move means earlier positions can't come back
+        state.play(&["e4", "e5"]);
+        state.play(&shuffle);
+        assert!(state.repetitions() == 2);
+    }
+
+    #[test]
+    fn test_move_rules() {
+        let fifty = state("8/8/4k3/8/8/3K3R/8/8 w - - 100 80");
+        assert!(fifty.outcome().is_none());
+        assert!(fifty.claimable_draw() == Some(Outcome::FiftyMoves));
+        let seventy_five = state("8/8/4k3/8/8/3K3R/8/8 w - - 150 105");
+        assert!(seventy_five.outcome() == Some(Outcome::SeventyFiveMoves));
+        assert!(seventy_five.result() == Some(GameResult::Draw));
+        // Mate on the last move still counts
+        let mate = state("R3k3/8/4K3/8/8/8/8/8 b - - 150 105");
+        assert!(
+            mate.outcome()
+                == Some(Outcome::Checkmate {
+                    winner: Color::White
+                })
+        );
+    }
+}
@@ -0,0 +1,219 @@
+//! Move path enumeration, for testing and debugging move generation
+//!
+//! `perft` counts the leaf nodes of the legal move tree to a given depth.
+//! The counts for many positions are well known, so any difference points
+//! to a bug. `divide` breaks the count down by root move, in the same format
+//! as other engines, so the faulty move can be found by comparing the two
+//! and descending into whichever root move disagrees.
+
+use std::fmt;
+
+use crate::game_state::GameState;
+use crate::moves::Move;
+
+/// Count the leaf nodes of the legal move tree to the given depth
+pub fn perft(state: &GameState, depth: u32) -> u64 {
+    count(&mut state.clone(), depth)
+}
+
+/// Count the leaf nodes below each legal root move
+///
+/// The depth includes the root moves, so at depth zero there's nothing to
+/// break down and the result is empty.
+pub fn divide(state: &GameState, depth: u32) -> Divide {
+    if depth == 0 {
+        return Divide { counts: Vec::new() };
+    }
+    let mut state = state.clone();
+    let counts = state
+        .legal_moves()
+        .iter()
+        .map(|mv| {
+            let undo = state.make_move(mv);
+            let nodes = count(&mut state, depth - 1);
+            state.unmake_move(undo);
+            (*mv, nodes)
+        })
+        .collect();
+    Divide { counts }
+}
+
+/// Count leaf nodes, making and unmaking moves on a single state
+fn count(state: &mut GameState, depth: u32) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    let moves = state.legal_moves();
+    // No need to make the moves just to count them
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+    moves
+        .iter()
+        .map(|mv| {
+            let undo = state.make_move(mv);
+            let nodes = count(state, depth - 1);
+            state.unmake_move(undo);
+            nodes
+        })
+        .sum()
+}
+
+/// Node counts per root move
+///
+/// Displays as one `e2e4: 20` line per move, followed by the total.
+/// Castling is written as the king's move, as in standard chess.
+#[derive(Debug)]
+pub struct Divide {
+    counts: Vec<(Move, u64)>,
+}
+impl Divide {
+    /// The node count below each root move
+    pub fn counts(&self) -> &[(Move, u64)] {
+        &self.counts
+    }
+    /// The node count across all root moves
+    pub fn total(&self) -> u64 {
+        self.counts.iter().map(|(_, count)| count).sum()
+    }
+}
+impl fmt::Display for Divide {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (mv, count) in self.counts.iter() {
+            writeln!(f, "{}: {}", mv, count)?;
+        }
+        writeln!(f)?;
+        write!(f, "Nodes searched: {}", self.total())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fen::STARTING_FEN;
+
+    const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+    const POSITION_3: &str = "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1";
+    const POSITION_4: &str = "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1";
+    const POSITION_5: &str = "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8";
+    const POSITION_6: &str =
+        "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10";
+
+    fn assert_perft(fen: &str, expected: &[u64]) {
+        let state = GameState::from_fen(fen).unwrap();
+        for (depth, count) in expected.iter().enumerate() {
+            assert!(
+                perft(&state, depth as u32 + 1) == *count,
+                "{} at depth {}",
+                fen,
+                depth + 1
+            );
+        }
+    }
+
+    #[test]
+    fn test_perft_initial() {
+        assert_perft(STARTING_FEN, &[20, 400, 8_902]);
+    }
+
+    #[test]
+    fn test_perft_kiwipete() {
+        assert_perft(KIWIPETE, &[48, 2_039]);
+    }
+
+    #[test]
+    fn test_perft_position_3() {
+        assert_perft(POSITION_3, &[14, 191, 2_812]);
+    }
+
+    #[test]
+    fn test_perft_position_4() {
+        assert_perft(POSITION_4, &[6, 264, 9_467]);
+    }
+
+    #[test]
+    fn test_perft_position_5() {
+        assert_perft(POSITION_5, &[44, 1_486]);
+    }
+
+    #[test]
+    fn test_perft_position_6() {
+        assert_perft(POSITION_6, &[46, 2_079]);
+    }
+
+    #[test]
+    fn test_divide() {
+        let divide = divide(&GameState::fresh_game(), 2);
+        assert!(divide.counts().len() == 20);
+        assert!(divide.total() == 400);
+        assert!(divide.counts().iter().all(|(_, count)| *count == 20));
+        let output = divide.to_string();
+        assert!(output.contains("e2e4: 20\n"));
+        assert!(output.contains("g1f3: 20\n"));
+        assert!(output.ends_with("\nNodes searched: 400"));
+
+        assert!(super::divide(&GameState::fresh_game(), 0)
+            .counts()
+            .is_empty());
+    }
+
+    #[test]
+    #[ignore]
+    fn test_perft_deep() {
+        assert_perft(STARTING_FEN, &[20, 400, 8_902, 197_281, 4_865_609]);
+        assert_perft(KIWIPETE, &[48, 2_039, 97_862, 4_085_603]);
+        assert_perft(POSITION_3, &[14, 191, 2_812, 43_238, 674_624]);
+        assert_perft(POSITION_4, &[6, 264, 9_467, 422_333]);
+        assert_perft(POSITION_5, &[44, 1_486, 62_379, 2_103_487]);
+        assert_perft(POSITION_6, &[46, 2_079, 89_890, 3_894_594]);
+    }
+
+    #[test]
+    #[ignore]
+    fn test_perft_tricky() {
+        let cases = [
+            // Avoid en passant captures which expose the king
+            ("3k4/3p4/8/K1P4r/8/8/8/8 b - - 0 1", 6, 1_134_888),
+            ("8/8/4k3/8/2p5/8/B2P2K1/8 w - - 0 1", 6, 1_015_133),
+            // En passant capture gives check
+            ("8/8/1k6/2b5/2pP4/8/5K2/8 b - d3 0 1", 6, 1_440_467),
+            // Castling gives check
+            ("5k2/8/8/8/8/8/8/4K2R w K - 0 1", 6, 661_072),
+            ("3k4/8/8/8/8/8/8/R3K3 w Q - 0 1", 6, 803_711),
+            // Castling rights lost by captures
+            ("r3k2r/1b4bq/8/8/8/8/7B/R3K2R w KQkq - 0 1", 4, 1_274_206),
+            // Castling prevented by attacked squares
+            ("r3k2r/8/3Q4/8/8/5q2/8/R3K2R b KQkq - 0 1", 4, 1_720_476),
+            // Promote out of check
+            ("2K2r2/4P3/8/8/8/8/8/3k4 w - - 0 1", 6, 3_821_001),
+            // Discovered check
+            ("8/8/1P2K3/8/2n5/1q6/8/5k2 b - - 0 1", 5, 1_004_658),
+            // Promote to give check
+            ("4k3/1P6/8/8/8/8/K7/8 w - - 0 1", 6, 217_342),
+            // Underpromote to avoid stalemate
+            ("8/P1k5/K7/8/8/8/8/8 w - - 0 1", 6, 92_683),
+            // Self stalemate
+            ("K1k5/8/P7/8/8/8/8/8 w - - 0 1", 6, 2_217),
+            // Stalemate and checkmate
+            ("8/k1P5/8/1K6/8/8/8/8 w - - 0 1", 7, 567_584),
+            ("8/8/2k5/5q2/5n2/8/5K2/8 b - - 0 1", 4, 23_527),
+        ];
+        for (fen, depth, expected) in cases.iter() {
+            let state = GameState::from_fen(fen).unwrap();
+            assert!(perft(&state, *depth) == *expected, "{}", fen);
+        }
+    }
+
+    #[test]
+    #[ignore]
+    fn test_perft_chess960() {
+        assert_perft(
+            "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+            &[21, 528, 12_189, 326_672],
+        );
+        assert_perft(
+            "2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9",
+            &[21, 807, 18_002, 667_366],
+        );
+    }
+}
@@ -0,0 +1,147 @@
+//! PEXT-indexed tables for sliding piece attacks
+//!
+//! On x86_64 CPUs with BMI2, the `pext` instruction gathers the occupied
+//! bits of a square's relevant occupancy mask into a dense index, so no
+//! magic numbers are needed. Whether the CPU supports it is checked once at
+//! runtime: where it doesn't, no tables are built and the magic tables are
+//! used instead.
+
+use std::arch::x86_64::_pext_u64;
+use std::sync::OnceLock;
+
+use crate::attacks::{slide, Direction, BISHOP_DIRECTIONS, ROOK_DIRECTIONS};
+use crate::bitboard::BitBoard;
+use crate::magic::{relevant_occupancy, subsets};
+use crate::square::{Square, SQUARES};
+
+static TABLES: OnceLock<Option<PextTables>> = OnceLock::new();
+
+/// Return the PEXT tables, or `None` if the CPU doesn't support BMI2
+pub fn tables() -> Option<&'static PextTables> {
+    TABLES
+        .get_or_init(|| {
+            if is_x86_feature_detected!("bmi2") {
+                Some(PextTables::generate())
+            } else {
+                None
+            }
+        })
+        .as_ref()
+}
+
+/// The mask and table offset for a single square
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    mask: u64,
+    offset: usize,
+}
+
+/// Attacks for rooks and bishops on every square, indexed with PEXT
+///
+/// These can only be constructed once BMI2 support has been detected.
+pub struct PextTables {
+    rooks: Vec<Entry>,
+    bishops: Vec<Entry>,
+    attacks: Vec<BitBoard>,
+}
+impl PextTables {
+    /// Squares attacked by a rook on the given square
+    pub fn rook_attacks(&self, square: &Square, occupancy: &BitBoard) -> BitBoard {
+        self.lookup(&self.rooks[*square as usize], occupancy)
+    }
+    /// Squares attacked by a bishop on the given square
+    pub fn bishop_attacks(&self, square: &Square, occupancy: &BitBoard) -> BitBoard {
+        self.lookup(&self.bishops[*square as usize], occupancy)
+    }
+    fn lookup(&self, entry: &Entry, occupancy: &BitBoard) -> BitBoard {
+        // SAFETY: tables are only built after BMI2 support is detected
+        let index = unsafe { pext(occupancy.positions(), entry.mask) };
+        self.attacks[entry.offset + index as usize]
+    }
+    fn generate() -> Self {
+        let mut attacks = Vec::new();
+        let mut build = |directions: &[Direction]| {
+            SQUARES
+                .iter()
+                .map(|square| {
+                    let from = BitBoard::from_square(square);
+                    let mask = relevant_occupancy(square, directions).positions();
+                    let offset = attacks.len();
+                    // Subsets come out in increasing order, which is the
+                    // same order as their PEXT indices
+                    attacks.extend(
+                        subsets(mask)
+                            .into_iter()
+                            .map(|occupancy| slide(&from, directions, &BitBoard::new(occupancy))),
+                    );
+                    Entry { mask, offset }
+                })
+                .collect::<Vec<Entry>>()
+        };
+        let rooks = build(&ROOK_DIRECTIONS);
+        let bishops = build(&BISHOP_DIRECTIONS);
+        Self {
+            rooks,
+            bishops,
+            attacks,
+        }
+    }
+}
+
+#[target_feature(enable = "bmi2")]
+unsafe fn pext(value: u64, mask: u64) -> u64 {
+    _pext_u64(value, mask)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::magic;
+
+    #[test]
+    fn test_backends_agree() {
+        let tables = match tables() {
+            Some(tables) => tables,
+            None => {
+                println!("skipping: this CPU has no BMI2, so there's nothing to compare");
+                return;
+            }
+        };
+        let magic = magic::tables();
+        // Every relevant occupancy for some squares, then a sample for all
+        for square in [Square::A1, Square::D4, Square::H8, Square::G2].iter() {
+            let masks = [
+                relevant_occupancy(square, &ROOK_DIRECTIONS),
+                relevant_occupancy(square, &BISHOP_DIRECTIONS),
+            ];
+            for mask in masks.iter() {
+                for occupancy in subsets(mask.positions()) {
+                    let occupancy = BitBoard::new(occupancy);
+                    assert!(
+                        tables.rook_attacks(square, &occupancy)
+                            == magic.rook_attacks(square, &occupancy)
+                    );
+                    assert!(
+                        tables.bishop_attacks(square, &occupancy)
+                            == magic.bishop_attacks(square, &occupancy)
+                    );
+                }
+            }
+        }
+        let mut occupancy = 0x0123_4567_89ab_cdefu64;
+        for _ in 0..256 {
+            occupancy ^= occupancy << 13;
+            occupancy ^= occupancy >> 7;
+            occupancy ^= occupancy << 17;
+            let sample = BitBoard::new(occupancy);
+            for square in SQUARES.iter() {
+                assert!(
+                    tables.rook_attacks(square, &sample) == magic.rook_attacks(square, &sample)
+                );
+                assert!(
+                    tables.bishop_attacks(square, &sample) == magic.bishop_attacks(square, &sample)
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,1118 @@
+//! Portable Game Notation
+//!
+//! A PGN file is a series of games, each a section of `[Name "value"]` tag
+//! pairs followed by movetext: the moves in SAN, with move numbers, comments
+//! in braces or after a semicolon, numeric annotation glyphs (`$1`, or `!`
+//! and friends), variations in parentheses, and finally the result.
+//!
+//! ```text
+//! [Event "Casual game"]
+//! [Result "1-0"]
+//!
+//! 1. e4 e5 2. Qh5 {Patzer} Nc6 (2... Nf6?? 3. Qxe5+) 3. Bc4 Nf6?? 4. Qxf7# 1-0
+//! ```
+//!
+//! `PgnReader` reads games one at a time from any buffered reader, so whole
+//! databases never need to be held in memory. A game which can't be read
+//! gives an error, and reading carries on with the next game.
+//!
+//! Games are written in export format: the seven tag roster first, then the
+//! other tags in ASCII order, and movetext wrapped at 80 columns with NAGs
+//! in `$` form. `[%clk 0:03:00]` clock annotations are kept apart from the
+//! comments they're read from, so they can be written back or left out.
+
+use std::error::Error;
+use std::fmt;
+use std::io::{BufRead, Lines};
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::board::Color;
+use crate::fen::{FenError, STARTING_FEN};
+use crate::game_state::GameState;
+use crate::moves::Move;
+use crate::san::SanError;
+
+/// The tags every game should have, in the order they're exported
+pub const SEVEN_TAG_ROSTER: [&str; 7] =
+    ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+
+/// The possible results of a game
+const RESULTS: [&str; 4] = ["1-0", "0-1", "1/2-1/2", "*"];
+
+/// How a game ended, as given by its result token
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PgnResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
+    /// The game is unfinished, or the result isn't known
+    Unknown,
+}
+impl PgnResult {
+    /// The result for a token like `1-0`
+    pub fn from_token(token: &str) -> Option<Self> {
+        match token {
+            "1-0" => Some(Self::WhiteWins),
+            "0-1" => Some(Self::BlackWins),
+            "1/2-1/2" => Some(Self::Draw),
+            "*" => Some(Self::Unknown),
+            _ => None,
+        }
+    }
+}
+impl fmt::Display for PgnResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let token = match self {
+            Self::WhiteWins => "1-0",
+            Self::BlackWins => "0-1",
+            Self::Draw => "1/2-1/2",
+            Self::Unknown => "*",
+        };
+        write!(f, "{}", token)
+    }
+}
+
+/// What was wrong with a game
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PgnError {
+    /// A tag pair wasn't a name and a quoted value in brackets
+    InvalidTag(String),
+    /// The `FEN` tag didn't hold a valid position
+    Fen(FenError),
+    /// A move wasn't valid SAN or wasn't legal in the position
+    Move(SanError),
+    /// Something in the movetext was out of place or not understood
+    UnexpectedToken(String),
+    /// A brace comment was never closed
+    UnterminatedComment,
+    /// A variation was never closed
+    UnterminatedVariation,
+    /// The underlying reader failed
+    Io(String),
+}
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidTag(tag) => write!(f, "invalid tag pair {:?}", tag),
+            Self::Fen(error) => write!(f, "invalid starting position: {}", error),
+            Self::Move(error) => write!(f, "{}", error),
+            Self::UnexpectedToken(token) => write!(f, "unexpected {:?} in movetext", token),
+            Self::UnterminatedComment => write!(f, "unterminated comment"),
+            Self::UnterminatedVariation => write!(f, "unterminated variation"),
+            Self::Io(error) => write!(f, "failed to read PGN: {}", error),
+        }
+    }
+}
+impl Error for PgnError {}
+impl From<FenError> for PgnError {
+    fn from(error: FenError) -> Self {
+        Self::Fen(error)
+    }
+}
+impl From<SanError> for PgnError {
+    fn from(error: SanError) -> Self {
+        Self::Move(error)
+    }
+}
+
+/// A move with its annotations, and any alternatives to it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PgnMove {
+    mv: Move,
+    nags: Vec<u8>,
+    clock: Option<Duration>,
+    comments: Vec<String>,
+    variations: Vec<PgnLine>,
+}
+impl PgnMove {
+    /// Construct a move with no annotations
+    pub const fn new(mv: Move) -> Self {
+        Self {
+            mv,
+            nags: Vec::new(),
+            clock: None,
+            comments: Vec::new(),
+            variations: Vec::new(),
+        }
+    }
+    /// Return the same move with another annotation glyph
+    pub fn with_nag(mut self, nag: u8) -> Self {
+        self.nags.push(nag);
+        self
+    }
+    /// Return the same move with the mover's remaining clock time
+    pub fn with_clock(mut self, clock: Duration) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+    /// Return the same move with another comment
+    pub fn with_comment(mut self, comment: &str) -> Self {
+        self.comments.push(comment.to_string());
+        self
+    }
+    /// Return the same move with another line played instead of it
+    pub fn with_variation(mut self, variation: PgnLine) -> Self {
+        self.variations.push(variation);
+        self
+    }
+    pub const fn mv(&self) -> Move {
+        self.mv
+    }
+    /// Numeric annotation glyphs, where `!` is 1, `?` is 2 and so on
+    pub fn nags(&self) -> &[u8] {
+        &self.nags
+    }
+    /// The time left on the mover's clock, from a `[%clk]` annotation
+    pub const fn clock(&self) -> Option<Duration> {
+        self.clock
+    }
+    /// Comments following the move
+    pub fn comments(&self) -> &[String] {
+        &self.comments
+    }
+    /// Lines played instead of this move, from the position before it
+    pub fn variations(&self) -> &[PgnLine] {
+        &self.variations
+    }
+}
+
+/// A sequence of moves, such as the mainline or a variation
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PgnLine {
+    comments: Vec<String>,
+    moves: Vec<PgnMove>,
+}
+impl PgnLine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Return the same line with another comment before its first move
+    pub fn with_comment(mut self, comment: &str) -> Self {
+        self.comments.push(comment.to_string());
+        self
+    }
+    /// Return the same line continued by a move
+    pub fn with_move(mut self, mv: PgnMove) -> Self {
+        self.moves.push(mv);
+        self
+    }
+    /// Comments before the line's first move
+    pub fn comments(&self) -> &[String] {
+        &self.comments
+    }
+    pub fn moves(&self) -> &[PgnMove] {
+        &self.moves
+    }
+}
+
+/// A game read from or to be written as PGN
+pub struct PgnGame {
+    tags: Vec<(String, String)>,
+    start: GameState,
+    mainline: PgnLine,
+    end: GameState,
+    result: PgnResult,
+}
+impl PgnGame {
+    /// Construct a game from a starting position, with no tags or moves
+    pub fn new(start: GameState) -> Self {
+        let end = start.clone();
+        Self {
+            tags: Vec::new(),
+            start,
+            mainline: PgnLine::default(),
+            end,
+            result: PgnResult::Unknown,
+        }
+    }
+    /// Parse a single game
+    ///
+    /// The game starts from the position in its `FEN` tag if it has one. If
+    /// the movetext has no result token, the `Result` tag is used instead.
+    pub fn parse(text: &str) -> Result<Self, PgnError> {
+        let mut tokens = tokenize(text)?.into_iter().peekable();
+        let mut tags = Vec::new();
+        while let Some(Token::Tag(_, _)) = tokens.peek() {
+            if let Some(Token::Tag(name, value)) = tokens.next() {
+                tags.push((name, value));
+            }
+        }
+        let tag = |name: &str| {
+            tags.iter()
+                .find(|(tag, _)| tag == name)
+                .map(|(_, value)| value.as_str())
+        };
+        let start = match tag("FEN") {
+            Some(fen) => GameState::from_fen(fen)?,
+            None => GameState::fresh_game(),
+        };
+
+        let mut lines = vec![Frame::new(start.clone())];
+        let mut result = None;
+        for token in tokens {
+            if result.is_some() {
+                return Err(PgnError::UnexpectedToken(token.to_string()));
+            }
+            match token {
+                Token::Symbol(symbol) if symbol.chars().all(|c| c.is_ascii_digit()) => {
+                    // Move numbers are only a guide for the reader
+                }
+                Token::Symbol(symbol) => match PgnResult::from_token(&symbol) {
+                    Some(_) if lines.len() > 1 => return Err(PgnError::UnterminatedVariation),
+                    Some(token) => result = Some(token),
+                    None => current(&mut lines).play(&symbol)?,
+                },
+                Token::Comment(comment) => {
+                    let line = &mut current(&mut lines).line;
+                    match (line.moves.last_mut(), extract_clock(&comment)) {
+                        (Some(last), Some((clock, rest))) => {
+                            last.clock = Some(clock);
+                            if !rest.is_empty() {
+                                last.comments.push(rest);
+                            }
+                        }
+                        (Some(last), None) => last.comments.push(comment),
+                        (None, _) => line.comments.push(comment),
+                    }
+                }
+                Token::Nag(nag) => match current(&mut lines).line.moves.last_mut() {
+                    Some(last) => last.nags.push(nag),
+                    None => return Err(PgnError::UnexpectedToken(token.to_string())),
+                },
+                Token::Open => match current(&mut lines).state.past_positions().next() {
+                    Some(before) => lines.push(Frame::new(before)),
+                    None => return Err(PgnError::UnexpectedToken(token.to_string())),
+                },
+                Token::Close if lines.len() > 1 => {
+                    let variation = lines.pop().map(|frame| frame.line).unwrap_or_default();
+                    if let Some(last) = current(&mut lines).line.moves.last_mut() {
+                        last.variations.push(variation);
+                    }
+                }
+                _ => return Err(PgnError::UnexpectedToken(token.to_string())),
+            }
+        }
+        if lines.len() > 1 {
+            return Err(PgnError::UnterminatedVariation);
+        }
+        let result = result
+            .or_else(|| tag("Result").and_then(PgnResult::from_token))
+            .unwrap_or(PgnResult::Unknown);
+        let Frame { line, state, .. } = lines.remove(0);
+        Ok(Self {
+            tags,
+            start,
+            mainline: line,
+            end: state,
+            result,
+        })
+    }
+    /// All tag pairs, in the order they were given
+    pub fn tags(&self) -> &[(String, String)] {
+        &self.tags
+    }
+    /// The value of a tag, if the game has it
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+    /// The position before the first move
+    pub const fn start(&self) -> &GameState {
+        &self.start
+    }
+    /// The moves played, with their annotations and variations
+    pub const fn mainline(&self) -> &PgnLine {
+        &self.mainline
+    }
+    /// The moves played, without annotations
+    pub fn moves(&self) -> impl Iterator<Item = Move> + '_ {
+        self.mainline.moves.iter().map(|mv| mv.mv)
+    }
+    /// The position after the last move of the mainline
+    pub const fn end(&self) -> &GameState {
+        &self.end
+    }
+    pub const fn result(&self) -> PgnResult {
+        self.result
+    }
+    /// Set a tag's value, replacing any it already has
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(tag, _)| tag == name) {
+            Some((_, existing)) => *existing = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+    /// Add a comment before the first move
+    pub fn add_comment(&mut self, comment: &str) {
+        self.mainline.comments.push(comment.to_string());
+    }
+    /// Continue the mainline with a move
+    ///
+    /// The move is assumed to be legal.
+    pub fn push(&mut self, mv: PgnMove) {
+        self.end.push(&mv.mv);
+        self.mainline.moves.push(mv);
+    }
+    pub fn set_result(&mut self, result: PgnResult) {
+        self.result = result;
+    }
+    /// Write the game in PGN export format, including what the options ask for
+    pub fn to_pgn(&self, options: &PgnOptions) -> String {
+        let mut pgn = String::new();
+        for (name, value) in self.export_tags() {
+            let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+            pgn.push_str(&format!("[{} \"{}\"]\n", name, value));
+        }
+        pgn.push('\n');
+        let mut words = Vec::new();
+        write_line(&self.start, &self.mainline, options, &mut words);
+        words.push(self.result.to_string());
+        pgn.push_str(&wrap(&words, options.width));
+        pgn.push('\n');
+        pgn
+    }
+    /// The seven tag roster, filled in where it's missing, followed by the
+    /// other tags in ASCII order
+    fn export_tags(&self) -> Vec<(String, String)> {
+        let mut tags = SEVEN_TAG_ROSTER
+            .iter()
+            .map(|name| {
+                let value = match (*name, self.tag(name)) {
+                    ("Result", _) => self.result.to_string(),
+                    (_, Some(value)) => value.to_string(),
+                    ("Date", None) => "????.??.??".to_string(),
+                    (_, None) => "?".to_string(),
+                };
+                (name.to_string(), value)
+            })
+            .collect::<Vec<(String, String)>>();
+        let mut others = self
+            .tags
+            .iter()
+            .filter(|(name, _)| {
+                !SEVEN_TAG_ROSTER.contains(&name.as_str()) && name != "SetUp" && name != "FEN"
+            })
+            .cloned()
+            .collect::<Vec<(String, String)>>();
+        let fen = self.start.to_fen();
+        if fen != STARTING_FEN {
+            others.push(("SetUp".to_string(), "1".to_string()));
+            others.push(("FEN".to_string(), fen));
+        }
+        others.sort_by(|(a, _), (b, _)| a.cmp(b));
+        tags.extend(others);
+        tags
+    }
+}
+impl FromStr for PgnGame {
+    type Err = PgnError;
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        Self::parse(text)
+    }
+}
+/// Games display in PGN export format with all their annotations
+impl fmt::Display for PgnGame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_pgn(&PgnOptions::new()))
+    }
+}
+
+/// What to include when writing PGN
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PgnOptions {
+    comments: bool,
+    nags: bool,
+    clocks: bool,
+    variations: bool,
+    width: usize,
+}
+impl PgnOptions {
+    /// Include every annotation, wrapping lines at 80 columns
+    pub const fn new() -> Self {
+        Self {
+            comments: true,
+            nags: true,
+            clocks: true,
+            variations: true,
+            width: 80,
+        }
+    }
+    /// Only the tags, moves and result
+    pub const fn moves_only() -> Self {
+        Self {
+            comments: false,
+            nags: false,
+            clocks: false,
+            variations: false,
+            ..Self::new()
+        }
+    }
+    pub const fn with_comments(self, comments: bool) -> Self {
+        Self { comments, ..self }
+    }
+    pub const fn with_nags(self, nags: bool) -> Self {
+        Self { nags, ..self }
+    }
+    pub const fn with_clocks(self, clocks: bool) -> Self {
+        Self { clocks, ..self }
+    }
+    pub const fn with_variations(self, variations: bool) -> Self {
+        Self { variations, ..self }
+    }
+    /// Return the same options wrapping movetext at a different width
+    pub const fn with_width(self, width: usize) -> Self {
+        Self { width, ..self }
+    }
+}
+impl Default for PgnOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Reads games one at a time from PGN text
+///
+/// Each game's text is gathered up before it's parsed. A game ends at a
+/// result token outside of any comment or variation, or where the tags of
+/// the next game begin, so a broken or unfinished game never takes the
+/// games after it down with it.
+pub struct PgnReader<R> {
+    lines: Lines<R>,
+    /// A line read past the end of the previous game, which starts the next
+    pending: Option<String>,
+    finished: bool,
+}
+impl<R: BufRead> PgnReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            lines: reader.lines(),
+            pending: None,
+            finished: false,
+        }
+    }
+}
+impl<R: BufRead> Iterator for PgnReader<R> {
+    type Item = Result<PgnGame, PgnError>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        let mut text = String::new();
+        let mut scan = Scan::default();
+        let mut in_movetext = false;
+        loop {
+            let line = match self.pending.take().map(Ok).or_else(|| self.lines.next()) {
+                Some(Ok(line)) => line,
+                Some(Err(error)) => {
+                    self.finished = true;
+                    return Some(Err(PgnError::Io(error.to_string())));
+                }
+                None => {
+                    self.finished = true;
+                    break;
+                }
+            };
+            let trimmed = line.trim();
+            if in_movetext && trimmed.starts_with('[') {
+                // Give up on a comment left open by a broken game once the
+                // next game's tags turn up
+                if !scan.in_comment || trimmed.starts_with("[Event ") {
+                    self.pending = Some(line);
+                    break;
+                }
+            }
+            if !scan.in_comment && trimmed.starts_with('%') {
+                continue;
+            }
+            if !scan.in_comment && !trimmed.is_empty() && !trimmed.starts_with('[') {
+                in_movetext = true;
+            }
+            let ends_game = scan.line(&line);
+            text.push_str(&line);
+            text.push('\n');
+            if ends_game {
+                break;
+            }
+        }
+        if text.trim().is_empty() {
+            None
+        } else {
+            Some(PgnGame::parse(&text))
+        }
+    }
+}
+
+/// Just enough of the movetext structure to find where a game ends
+#[derive(Debug, Default)]
+struct Scan {
+    in_comment: bool,
+    depth: u32,
+}
+impl Scan {
+    /// Follow a line, returning whether it ends with a result token
+    fn line(&mut self, line: &str) -> bool {
+        let mut last = String::new();
+        for c in line.chars() {
+            if self.in_comment {
+                self.in_comment = c != '}';
+                continue;
+            }
+            match c {
+                '{' => self.in_comment = true,
+                ';' => break,
+                '(' => self.depth += 1,
+                ')' => self.depth = self.depth.saturating_sub(1),
+                '[' if line.trim_start().starts_with('[') => return false,
+                _ => {}
+            }
+            if c.is_whitespace() || "{}()".contains(c) {
+                last.clear();
+            } else {
+                last.push(c);
+            }
+        }
+        !self.in_comment && self.depth == 0 && RESULTS.contains(&last.as_str())
+    }
+}
+
+/// A line of play being read, and the position at its end
+///
+/// The state's history holds the line's moves, so a variation on the last
+/// of them starts from the position that move is taken back to.
+struct Frame {
+    line: PgnLine,
+    state: GameState,
+}
+impl Frame {
+    fn new(state: GameState) -> Self {
+        Self {
+            line: PgnLine::default(),
+            state,
+        }
+    }
+    /// Play a move given in SAN
+    fn play(&mut self, san: &str) -> Result<(), PgnError> {
+        let mv = self.state.parse_san(san)?;
+        self.state.push(&mv);
+        self.line.moves.push(PgnMove::new(mv));
+        Ok(())
+    }
+}
+
+/// The innermost line being read
+fn current(lines: &mut [Frame]) -> &mut Frame {
+    lines.last_mut().expect("the mainline is never closed")
+}
+
+/// Add the movetext words for a line played from the given position
+///
+/// A move number is given before every white move, and before a black move
+/// wherever the movetext was interrupted by a comment or variation.
+fn write_line(state: &GameState, line: &PgnLine, options: &PgnOptions, words: &mut Vec<String>) {
+    let mut state = state.without_history();
+    let mut number = true;
+    if options.comments {
+        for comment in line.comments.iter() {
+            push_comment(words, comment);
+        }
+    }
+    for mv in line.moves.iter() {
+        let fullmove = state.meta().fullmove_number();
+        match state.turn() {
+            Color::White => words.push(format!("{}.", fullmove)),
+            Color::Black if number => words.push(format!("{}...", fullmove)),
+            Color::Black => {}
+        }
+        words.push(state.to_san(&mv.mv));
+        number = false;
+        if options.nags {
+            words.extend(mv.nags.iter().map(|nag| format!("${}", nag)));
+        }
+        if let (true, Some(clock)) = (options.clocks, mv.clock) {
+            push_comment(words, &format!("[%clk {}]", format_clock(clock)));
+            number = true;
+        }
+        if options.comments {
+            for comment in mv.comments.iter() {
+                push_comment(words, comment);
+                number = true;
+            }
+        }
+        if options.variations {
+            for variation in mv.variations.iter().filter(|line| !line.moves.is_empty()) {
+                let start = words.len();
+                write_line(&state, variation, options, words);
+                words[start].insert(0, '(');
+                if let Some(last) = words.last_mut() {
+                    last.push(')');
+                }
+                number = true;
+            }
+        }
+        state.push(&mv.mv);
+    }
+}
+
+/// Add a comment as words, so long comments can be wrapped
+fn push_comment(words: &mut Vec<String>, comment: &str) {
+    let start = words.len();
+    words.extend(comment.split_whitespace().map(str::to_string));
+    if words.len() == start {
+        words.push(String::new());
+    }
+    words[start].insert(0, '{');
+    if let Some(last) = words.last_mut() {
+        last.push('}');
+    }
+}
+
+/// Join words with spaces, starting a new line before any word which would
+/// take a line past the given width
+fn wrap(words: &[String], width: usize) -> String {
+    let mut text = String::new();
+    let mut length = 0;
+    for word in words.iter() {
+        let word_length = word.chars().count();
+        if length > 0 && length + 1 + word_length > width {
+            text.push('\n');
+            length = 0;
+        } else if length > 0 {
+            text.push(' ');
+            length += 1;
+        }
+        text.push_str(word);
+        length += word_length;
+    }
+    text
+}
+
+/// Take a `[%clk h:mm:ss]` annotation out of a comment, returning the clock
+/// time and the rest of the comment
+fn extract_clock(comment: &str) -> Option<(Duration, String)> {
+    let start = comment.find("[%clk")?;
+    let end = start + comment[start..].find(']')?;
+    let clock = parse_clock(comment[start + 5..end].trim())?;
+    let rest = format!("{} {}", comment[..start].trim(), comment[end + 1..].trim());
+    Some((clock, rest.trim().to_string()))
+}
+
+/// Parse a clock time like `1:02:03` or `0:00:09.5`
+fn parse_clock(clock: &str) -> Option<Duration> {
+    let digits = |part: &str| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit());
+    let mut parts = clock.split(':');
+    let (hours, minutes, seconds) = (parts.next()?, parts.next()?, parts.next()?);
+    let (seconds, fraction) = seconds.split_once('.').unwrap_or((seconds, "0"));
+    if parts.next().is_some()
+        || ![hours, minutes, seconds, fraction]
+            .iter()
+            .all(|part| digits(part))
+        || fraction.len() > 9
+    {
+        return None;
+    }
+    let (hours, minutes, seconds) = (
+        hours.parse::<u64>().ok()?,
+        minutes.parse::<u64>().ok()?,
+        seconds.parse::<u64>().ok()?,
+    );
+    if minutes >= 60 || seconds >= 60 {
+        return None;
+    }
+    let nanos = format!("{:0<9}", fraction).parse().ok()?;
+    Some(Duration::new(hours * 3600 + minutes * 60 + seconds, nanos))
+}
+
+/// Format a clock time as `h:mm:ss`, with any fraction of a second
+fn format_clock(clock: Duration) -> String {
+    let seconds = clock.as_secs();
+    let mut text = format!(
+        "{}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    );
+    if clock.subsec_nanos() > 0 {
+        let fraction = format!("{:09}", clock.subsec_nanos());
+        text.push('.');
+        text.push_str(fraction.trim_end_matches('0'));
+    }
+    text
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Tag(String, String),
+    /// A move, move number or result
+    Symbol(String),
+    Comment(String),
+    Nag(u8),
+    Open,
+    Close,
+}
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tag(name, value) => write!(f, "[{} {:?}]", name, value),
+            Self::Symbol(symbol) => write!(f, "{}", symbol),
+            Self::Comment(comment) => write!(f, "{{{}}}", comment),
+            Self::Nag(nag) => write!(f, "${}", nag),
+            Self::Open => write!(f, "("),
+            Self::Close => write!(f, ")"),
+        }
+    }
+}
+
+/// The glyph for the traditional suffix annotations
+fn suffix_nag(suffix: &str) -> Option<u8> {
+    match suffix {
+        "!" => Some(1),
+        "?" => Some(2),
+        "!!" => Some(3),
+        "??" => Some(4),
+        "!?" => Some(5),
+        "?!" => Some(6),
+        _ => None,
+    }
+}
+
+/// Split a game's text into tag pairs and movetext tokens
+fn tokenize(text: &str) -> Result<Vec<Token>, PgnError> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    let mut line_start = true;
+    while let Some(c) = chars.next() {
+        let at_line_start = line_start;
+        line_start = c == '\n';
+        let mut take_while = |first: Option<char>, include: &dyn Fn(char) -> bool| {
+            let mut taken = first.map(String::from).unwrap_or_default();
+            while let Some(next) = chars.next_if(|next| include(*next)) {
+                taken.push(next);
+            }
+            taken
+        };
+        match c {
+            // An escaped line, for software to use however it likes
+            '%' if at_line_start => {
+                take_while(None, &|next| next != '\n');
+            }
+            '[' => {
+                let mut quoted = false;
+                let mut escaped = false;
+                let pair = take_while(None, &|next| next != '\n');
+                let end = pair.char_indices().find_map(|(i, next)| {
+                    let end = next == ']' && !quoted;
+                    quoted ^= next == '"' && !escaped;
+                    escaped = next == '\\' && !escaped;
+                    end.then_some(i)
+                });
+                let end = end.ok_or_else(|| PgnError::InvalidTag(pair.clone()))?;
+                let (name, value) =
+                    parse_tag(&pair[..end]).ok_or_else(|| PgnError::InvalidTag(pair.clone()))?;
+                tokens.push(Token::Tag(name, value));
+                // Anything after the tag on the same line is more text
+                let rest = pair[end + 1..].to_string();
+                if !rest.trim().is_empty() {
+                    tokens.extend(tokenize(&rest)?);
+                }
+                line_start = true;
+            }
+            '{' => {
+                let comment = take_while(None, &|next| next != '}');
+                if chars.next().is_none() {
+                    return Err(PgnError::UnterminatedComment);
+                }
+                line_start = comment.ends_with('\n');
+                tokens.push(Token::Comment(comment.trim().to_string()));
+            }
+            ';' => {
+                let comment = take_while(None, &|next| next != '\n');
+                tokens.push(Token::Comment(comment.trim().to_string()));
+            }
+            '$' => {
+                let digits = take_while(None, &|next| next.is_ascii_digit());
+                let nag = digits
+                    .parse()
+                    .map_err(|_| PgnError::UnexpectedToken(format!("${}", digits)))?;
+                tokens.push(Token::Nag(nag));
+            }
+            '!' | '?' => {
+                let suffix = take_while(Some(c), &|next| next == '!' || next == '?');
+                let nag = suffix_nag(&suffix).ok_or(PgnError::UnexpectedToken(suffix))?;
+                tokens.push(Token::Nag(nag));
+            }
+            '(' => tokens.push(Token::Open),
+            ')' => tokens.push(Token::Close),
+            '*' => tokens.push(Token::Symbol(c.to_string())),
+            '.' => {}
+            c if c.is_whitespace() => {}
+            c if c.is_ascii_alphanumeric() => {
+                let symbol = take_while(Some(c), &|next| {
+                    next.is_ascii_alphanumeric() || "_+#=:-/".contains(next)
+                });
+                tokens.push(Token::Symbol(symbol));
+            }
+            _ => return Err(PgnError::UnexpectedToken(c.to_string())),
+        }
+    }
+    Ok(tokens)
+}
+
+/// Split the inside of a tag pair into its name and unescaped value
+fn parse_tag(pair: &str) -> Option<(String, String)> {
+    let pair = pair.trim();
+    let name_end = pair.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))?;
+    let (name, rest) = pair.split_at(name_end);
+    let quoted = rest.trim_start().strip_prefix('"')?.strip_suffix('"')?;
+    if name.is_empty() {
+        return None;
+    }
+    let mut value = String::with_capacity(quoted.len());
+    let mut chars = quoted.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => value.push(chars.next()?),
+            '"' => return None,
+            _ => value.push(c),
+        }
+    }
+    Some((name.to_string(), value))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::board::Piece;
+
+    const GAME: &str = r#"[Event "Casual game"]
+[Site "London ENG"]
+[Date "1851.06.21"]
+[Round "?"]
+[White "Anderssen, Adolf"]
+[Black "Kieseritzky, Lionel"]
+[Result "1-0"]
+[Annotator "Someone \"quoted\""]
+
+1. e4 e5 2. f4 exf4 3. Bc4 Qh4+ 4. Kf1 b5 {The bishop is offered} 5. Bxb5 Nf6
+6. Nf3 Qh6 7. d3 Nh5 8. Nh4 Qg5 9. Nf5 c6 10. g4 Nf6 11. Rg1! cxb5 12. h4 Qg6
+13. h5 Qg5 14. Qf3 Ng8 15. Bxf4 Qf6 16. Nc3 Bc5 17. Nd5 Qxb2 18. Bd6 Bxg1
+(18... Qxa1+ 19. Ke2 Qb2 $13) 19. e5 Qxa1+ 20. Ke2 Na6 21. Nxg7+ Kd8 22. Qf6+
+Nxf6 23. Be7# 1-0
+"#;
+
+    #[test]
+    fn test_tags() {
+        let game = PgnGame::parse(GAME).unwrap();
+        assert!(game.tags().len() == 8);
+        for (roster, (name, _)) in SEVEN_TAG_ROSTER.iter().zip(game.tags().iter()) {
+            assert!(roster == name);
+        }
+        assert!(game.tag("White") == Some("Anderssen, Adolf"));
+        assert!(game.tag("Annotator") == Some("Someone \"quoted\""));
+        assert!(game.tag("ECO").is_none());
+    }
+
+    #[test]
+    fn test_mainline() {
+        let game = PgnGame::parse(GAME).unwrap();
+        assert!(game.moves().count() == 45);
+        assert!(game.result() == PgnResult::WhiteWins);
+        assert!(game.end().in_check());
+        assert!(game.end().legal_moves().is_empty());
+        let moves = game.mainline().moves();
+        assert!(moves[7].comments() == ["The bishop is offered"]);
+        assert!(moves[20].nags() == [1]);
+        assert!(moves[35].variations().len() == 1);
+        let variation = &moves[35].variations()[0];
+        assert!(variation.moves().len() == 3);
+        assert!(variation.moves()[2].nags() == [13]);
+        assert!(game.start().to_fen() == crate::fen::STARTING_FEN);
+    }
+
+    #[test]
+    fn test_annotations() {
+        let text = "{Start} 1. e4 ; line comment\n e5!? (1... c5 {Sicilian} 2. Nf3 (2. c3 d5)) (1... e6 $2) \
+                    2. Nf3?! $14 {a} {b} *";
+        let game = PgnGame::parse(text).unwrap();
+        let line = game.mainline();
+        assert!(line.comments() == ["Start"]);
+        assert!(line.moves()[0].comments() == ["line comment"]);
+        assert!(line.moves()[1].nags() == [5]);
+        let variations = line.moves()[1].variations();
+        assert!(variations.len() == 2);
+        assert!(variations[0].moves()[0].comments() == ["Sicilian"]);
+        assert!(variations[0].moves()[1].variations()[0].moves().len() == 2);
+        assert!(variations[1].moves()[0].nags() == [2]);
+        assert!(line.moves()[2].nags() == [6, 14]);
+        assert!(line.moves()[2].comments() == ["a", "b"]);
+        assert!(game.result() == PgnResult::Unknown);
+    }
+
+    #[test]
+    fn test_starting_position() {
+        let text = "[SetUp \"1\"]\n[FEN \"4k3/1P6/8/8/8/8/8/4K3 w - - 0 40\"]\n\n\
+                    40. b8=Q+ Kd7 41. Qb5+ 1/2-1/2";
+        let game = PgnGame::parse(text).unwrap();
+        assert!(game.moves().next().unwrap().promotion() == Some(Piece::Queen));
+        assert!(game.end().meta().fullmove_number() == 41);
+        assert!(game.result() == PgnResult::Draw);
+
+        // Black to move continues the numbering with an ellipsis
+        let text = "[FEN \"4k3/8/8/8/8/8/8/4K2R b K - 0 1\"]\n1... Kd7 2. O-O *";
+        let game = PgnGame::parse(text).unwrap();
+        assert!(game.moves().nth(1).unwrap().is_castle());
+    }
+
+    #[test]
+    fn test_result_from_tag() {
+        let game = PgnGame::parse("[Result \"0-1\"]\n1. f3 e5 2. g4 Qh4#").unwrap();
+        assert!(game.result() == PgnResult::BlackWins);
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |text: &str| PgnGame::parse(text).err().unwrap();
+        assert!(error("[Event Casual]\n*") == PgnError::InvalidTag("Event Casual]".to_string()));
+        assert!(
+            error("[Event \"Casual\"\n*") == PgnError::InvalidTag("Event \"Casual\"".to_string())
+        );
+        assert!(matches!(error("[FEN \"8/8 w - -\"]\n*"), PgnError::Fen(_)));
+        assert!(error("1. e4 e4") == PgnError::Move(SanError::Illegal("e4".to_string())));
+        assert!(error("1. e4 {open") == PgnError::UnterminatedComment);
+        assert!(error("1. e4 (1. d4") == PgnError::UnterminatedVariation);
+        assert!(error("1. e4 (1. d4 1-0") == PgnError::UnterminatedVariation);
+        assert!(error("( 1. e4 )") == PgnError::UnexpectedToken("(".to_string()));
+        assert!(error("1. e4 )") == PgnError::UnexpectedToken(")".to_string()));
+        assert!(error("1. e4 e5 1-0 2. Nf3") == PgnError::UnexpectedToken("2".to_string()));
+        assert!(error("1. e4 ?!?") == PgnError::UnexpectedToken("?!?".to_string()));
+        assert!(error("1. e4 & e5") == PgnError::UnexpectedToken("&".to_string()));
+    }
+
+    #[test]
+    fn test_reader() {
+        let text = format!(
+            "{}\n[Event \"Broken\"]\n\n1. e4 e5 2. Ke3 *\n\n\
+             [Event \"Unfinished\"]\n\n1. d4 {{left open\n\
+             [Event \"Recovered\"]\n\n% skip this\n1. c4 {{[%clk 0:03:00]}} 1-0\n\
+             1. Nf3 *\n",
+            GAME
+        );
+        let games = PgnReader::new(text.as_bytes()).collect::<Vec<_>>();
+        assert!(games.len() == 5);
+        assert!(games[0].as_ref().unwrap().moves().count() == 45);
+        assert!(
+            games[1].as_ref().err() == Some(&PgnError::Move(SanError::Illegal("Ke3".to_string())))
+        );
+        assert!(games[2].as_ref().err() == Some(&PgnError::UnterminatedComment));
+        let recovered = games[3].as_ref().unwrap();
+        assert!(recovered.tag("Event") == Some("Recovered"));
+        let first = &recovered.mainline().moves()[0];
+        assert!(first.clock() == Some(Duration::from_secs(180)));
+        assert!(first.comments().is_empty());
+        // A game without tags is ended by the previous one's result
+        assert!(games[4].as_ref().unwrap().moves().count() == 1);
+    }
+
+    #[test]
+    fn test_write() {
+        let text = "[White \"Kasparov, \\\"Garry\\\"\"]\n[ECO \"C20\"]\n[Black \"Deep Blue\"]\n\n\
+                    {Opening} 1. e4 e5 2. Qh5 {Patzer} Nc6 (2... Nf6?? 3. Qxe5+) \
+                    3. Bc4 {[%clk 0:02:59.5]} Nf6?? {Oops, a very long comment which has to \
+                    be wrapped} 4. Qxf7# 1-0";
+        let game = PgnGame::parse(text).unwrap();
+        let expected = r#"[Event "?"]
+[Site "?"]
+[Date "????.??.??"]
+[Round "?"]
+[White "Kasparov, \"Garry\""]
+[Black "Deep Blue"]
+[Result "1-0"]
+[ECO "C20"]
+
+{Opening} 1. e4 e5 2. Qh5 {Patzer} 2... Nc6 (2... Nf6 $4 3. Qxe5+) 3. Bc4 {[%clk
+0:02:59.5]} 3... Nf6 $4 {Oops, a very long comment which has to be wrapped} 4.
+Qxf7# 1-0
+"#;
+        assert!(game.to_string() == expected);
+
+        let movetext = |options: &PgnOptions| {
+            let pgn = game.to_pgn(options);
+            pgn.split("\n\n").nth(1).unwrap().to_string()
+        };
+        assert!(
+            movetext(&PgnOptions::moves_only()) == "1. e4 e5 2. Qh5 Nc6 3. Bc4 Nf6 4. Qxf7# 1-0\n"
+        );
+        assert!(
+            movetext(&PgnOptions::new().with_comments(false).with_nags(false))
+                == "1. e4 e5 2. Qh5 Nc6 (2... Nf6 3. Qxe5+) 3. Bc4 {[%clk 0:02:59.5]} 3... Nf6 4.\n\
+                    Qxf7# 1-0\n"
+        );
+        assert!(
+            movetext(&PgnOptions::moves_only().with_width(20))
+                == "1. e4 e5 2. Qh5 Nc6\n3. Bc4 Nf6 4. Qxf7#\n1-0\n"
+        );
+    }
+
+    #[test]
+    fn test_write_round_trip() {
+        let game = PgnGame::parse(GAME).unwrap();
+        let pgn = game.to_string();
+        assert!(pgn.lines().all(|line| line.chars().count() <= 80));
+        let again = PgnGame::parse(&pgn).unwrap();
+        assert!(again.tags() == game.tags());
+        assert!(again.mainline() == game.mainline());
+        assert!(again.result() == game.result());
+        assert!(again.to_string() == pgn);
+    }
+
+    #[test]
+    fn test_build() {
+        let start = GameState::from_fen("4k3/8/8/8/8/8/8/4K2R b K - 0 30").unwrap();
+        let mut game = PgnGame::new(start);
+        let king = game.end().parse_san("Kd7").unwrap();
+        game.push(PgnMove::new(king).with_clock(Duration::from_millis(61_250)));
+        let castle = game.end().parse_san("O-O").unwrap();
+        let alternative = game.end().parse_san("Rh7+").unwrap();
+        game.push(
+            PgnMove::new(castle)
+                .with_nag(3)
+                .with_variation(PgnLine::new().with_move(PgnMove::new(alternative))),
+        );
+        game.set_tag("Event", "Endgame");
+        game.set_tag("Event", "Endgame study");
+        game.set_tag("Annotator", "Nobody");
+        game.add_comment("Black to move");
+        game.set_result(PgnResult::Draw);
+        assert!(game.end().turn() == Color::Black);
+        let expected = "[Event \"Endgame study\"]\n[Site \"?\"]\n[Date \"????.??.??\"]\n\
+                        [Round \"?\"]\n[White \"?\"]\n[Black \"?\"]\n[Result \"1/2-1/2\"]\n\
+                        [Annotator \"Nobody\"]\n[FEN \"4k3/8/8/8/8/8/8/4K2R b K - 0 30\"]\n\
+                        [SetUp \"1\"]\n\n{Black to move} 30... Kd7 {[%clk 0:01:01.25]} \
+                        31. O-O $3 (31. Rh7+) 1/2-1/2\n";
+        assert!(game.to_string() == expected);
+    }
+
+    #[test]
+    fn test_clocks() {
+        assert!(parse_clock("0:03:00") == Some(Duration::from_secs(180)));
+        assert!(parse_clock("1:02:03.5") == Some(Duration::from_millis(3_723_500)));
+        for invalid in ["", "3:00", "0:60:00", "0:00:0a", "0:00:00.", "0:0:0:0"].iter() {
+            assert!(parse_clock(invalid).is_none(), "{}", invalid);
+        }
+        assert!(format_clock(Duration::from_secs(3_723)) == "1:02:03");
+        assert!(format_clock(Duration::from_millis(9_400)) == "0:00:09.4");
+        assert!(
+            extract_clock("before [%clk 0:00:10] after")
+                == Some((Duration::from_secs(10), "before after".to_string()))
+        );
+        assert!(extract_clock("[%eval 0.3]").is_none());
+    }
+
+    #[test]
+    fn test_reader_empty() {
+        assert!(PgnReader::new("".as_bytes()).next().is_none());
+        assert!(PgnReader::new("\n\n  \n".as_bytes()).next().is_none());
+    }
+}
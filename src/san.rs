@@ -0,0 +1,341 @@
+//! Standard Algebraic Notation
+//!
+//! SAN names a move by the moving piece and its destination, adding just
+//! enough of the origin square to tell apart pieces of the same kind which
+//! could reach it:
+//!
+//! ```text
+//! e4  Nbd7  R1e2  exd8=Q+  O-O-O#
+//! ```
+//!
+//! Since SAN only makes sense relative to a position, conversion both ways
+//! happens on a game state.
+
+use std::error::Error;
+use std::fmt;
+
+use crate::board::Piece;
+use crate::castling::CastleSide;
+use crate::game_state::GameState;
+use crate::moves::{Move, MoveKind};
+use crate::square::{File, Rank, Square};
+
+/// Why SAN couldn't be read as a move
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SanError {
+    /// The text isn't SAN at all
+    Invalid(String),
+    /// No legal move matches
+    Illegal(String),
+    /// More than one legal move matches
+    Ambiguous(String),
+}
+impl fmt::Display for SanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Invalid(san) => write!(f, "invalid SAN {:?}", san),
+            Self::Illegal(san) => write!(f, "illegal move {:?}", san),
+            Self::Ambiguous(san) => write!(f, "ambiguous move {:?}", san),
+        }
+    }
+}
+impl Error for SanError {}
+
+/// The parts of a SAN move, other than castling
+struct SanParts {
+    piece: Piece,
+    from_file: Option<File>,
+    from_rank: Option<Rank>,
+    to: Square,
+    promotion: Option<Piece>,
+}
+
+impl GameState {
+    /// Write a legal move in SAN, with a `+` or `#` suffix for check or mate
+    pub fn to_san(&self, mv: &Move) -> String {
+        let mut san = match mv.kind() {
+            MoveKind::Castle(CastleSide::King) => "O-O".to_string(),
+            MoveKind::Castle(CastleSide::Queen) => "O-O-O".to_string(),
+            _ => self.san_without_suffix(mv),
+        };
+        // Only the position matters, so don't copy the whole game
+        let next = self.without_history().apply_move(mv);
+        if next.in_check() {
+            san.push(if next.legal_moves().is_empty() {
+                '#'
+            } else {
+                '+'
+            });
+        }
+        san
+    }
+    fn san_without_suffix(&self, mv: &Move) -> String {
+        let from = mv.from();
+        let piece = match self.board().occupant(&from) {
+            Some((piece, _)) => piece,
+            None => Piece::Pawn,
+        };
+        let mut san = String::new();
+        if piece == Piece::Pawn {
+            if mv.is_capture() {
+                san.push(from.file().to_char());
+            }
+        } else {
+            san.push(piece.letter());
+            // Other pieces of the same kind which could go to the same square
+            let others = self
+                .legal_moves()
+                .into_iter()
+                .filter(|other| other.to() == mv.to() && other.from() != from && !other.is_castle())
+                .filter(|other| {
+                    self.board()
+                        .occupant(&other.from())
+                        .is_some_and(|(other, _)| other == piece)
+                })
+                .map(|other| other.from())
+                .collect::<Vec<Square>>();
+            if !others.is_empty() {
+                let shares_file = others.iter().any(|other| other.file() == from.file());
+                let shares_rank = others.iter().any(|other| other.rank() == from.rank());
+                if !shares_file {
+                    san.push(from.file().to_char());
+                } else if !shares_rank {
+                    san.push(from.rank().to_char());
+                } else {
+                    san.push(from.file().to_char());
+                    san.push(from.rank().to_char());
+                }
+            }
+        }
+        if mv.is_capture() {
+            san.push('x');
+        }
+        san.push_str(&mv.to().to_string().to_lowercase());
+        if let Some(promotion) = mv.promotion() {
+            san.push('=');
+            san.push(promotion.letter());
+        }
+        san
+    }
+    /// Find the legal move described by some SAN
+    ///
+    /// Check, mate and annotation suffixes (`+#!?`) are ignored, as is
+    /// whether a capture is marked with `x`. Castling may be written with
+    /// letter O or digit 0.
+    pub fn parse_san(&self, san: &str) -> Result<Move, SanError> {
+        let trimmed = san.trim_end_matches(['+', '#', '!', '?']);
+        let castle = match trimmed {
+            "O-O" | "0-0" => Some(CastleSide::King),
+            "O-O-O" | "0-0-0" => Some(CastleSide::Queen),
+            _ => None,
+        };
+        let matches = match castle {
+            Some(side) => self
+                .legal_moves()
+                .into_iter()
+                .filter(|mv| mv.kind() == MoveKind::Castle(side))
+                .collect::<Vec<Move>>(),
+            None => {
+                let parts =
+                    parse_parts(trimmed).ok_or_else(|| SanError::Invalid(san.to_string()))?;
+                self.legal_moves()
+                    .into_iter()
+                    .filter(|mv| self.matches(mv, &parts))
+                    .collect::<Vec<Move>>()
+            }
+        };
+        match matches.len() {
+            0 => Err(SanError::Illegal(san.to_string())),
+            1 => Ok(matches[0]),
+            _ => Err(SanError::Ambiguous(san.to_string())),
+        }
+    }
+    fn matches(&self, mv: &Move, parts: &SanParts) -> bool {
+        let from = mv.from();
+        !mv.is_castle()
+            && mv.to() == parts.to
+            && mv.promotion() == parts.promotion
+            && parts.from_file.is_none_or(|f| from.file() == f)
+            && parts.from_rank.is_none_or(|r| from.rank() == r)
+            && self
+                .board()
+                .occupant(&from)
+                .is_some_and(|(piece, _)| piece == parts.piece)
+    }
+}
+
+/// Split SAN for anything but castling into its parts, working in from
+/// either end
+fn parse_parts(san: &str) -> Option<SanParts> {
+    let mut chars = san.chars().collect::<Vec<char>>();
+
+    let mut promotion = None;
+    if let Some(piece) = chars.last().and_then(|c| Piece::from_letter(*c)) {
+        chars.pop();
+        // The `=` is sometimes left out
+        if chars.last() == Some(&'=') {
+            chars.pop();
+        }
+        promotion = Some(piece);
+    }
+
+    if chars.len() < 2 {
+        return None;
+    }
+    let to_rank = Rank::from_char(chars.pop()?)?;
+    let to_file = File::from_char(chars.pop()?)?;
+
+    let piece = match chars.first().and_then(|c| Piece::from_letter(*c)) {
+        Some(piece) => {
+            chars.remove(0);
+            piece
+        }
+        None => Piece::Pawn,
+    };
+    if chars.last() == Some(&'x') {
+        chars.pop();
+    }
+    let (from_file, from_rank) = match chars.as_slice() {
+        [] => (None, None),
+        [c] => match (File::from_char(*c), Rank::from_char(*c)) {
+            (None, None) => return None,
+            disambiguation => disambiguation,
+        },
+        [f, r] => (Some(File::from_char(*f)?), Some(Rank::from_char(*r)?)),
+        _ => return None,
+    };
+    let promotable = matches!(
+        promotion,
+        None | Some(Piece::Queen | Piece::Rook | Piece::Bishop | Piece::Knight)
+    );
+    if !promotable || (promotion.is_some() && piece != Piece::Pawn) {
+        return None;
+    }
+    Some(SanParts {
+        piece,
+        from_file,
+        from_rank,
+        to: Square::new(to_file, to_rank),
+        promotion,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn state(fen: &str) -> GameState {
+        GameState::from_fen(fen).unwrap()
+    }
+
+    /// SAN for every legal move in a position
+    fn all_san(state: &GameState) -> Vec<String> {
+        state
+            .legal_moves()
+            .into_iter()
+            .map(|mv| state.to_san(&mv))
+            .collect()
+    }
+
+    #[test]
+    fn test_opening_moves() {
+        let state = GameState::fresh_game();
+        let moves = all_san(&state);
+        assert!(moves.len() == 20);
+        for san in ["e4", "a3", "h4", "Nf3", "Na3"].iter() {
+            assert!(moves.contains(&san.to_string()), "{}", san);
+        }
+        let mv = state.parse_san("Nf3").unwrap();
+        assert!(mv.from() == Square::G1 && mv.to() == Square::F3);
+        let mv = state.parse_san("e4").unwrap();
+        assert!(mv.from() == Square::E2 && mv.to() == Square::E4);
+    }
+
+    #[test]
+    fn test_disambiguation() {
+        let moves = all_san(&state("1n2k3/8/5n2/8/8/8/8/4K3 b - - 0 1"));
+        assert!(moves.contains(&"Nbd7".to_string()));
+        assert!(moves.contains(&"Nfd7".to_string()));
+        let moves = all_san(&state("4k3/8/8/R7/8/8/8/R3K3 w - - 0 1"));
+        assert!(moves.contains(&"R1a3".to_string()));
+        assert!(moves.contains(&"R5a3".to_string()));
+        // Queens on e4, h4 and h1 can all reach e1
+        let moves = all_san(&state("8/2k5/8/8/4Q2Q/8/8/K6Q w - - 0 1"));
+        assert!(moves.contains(&"Qh4e1".to_string()));
+        assert!(moves.contains(&"Qee1".to_string()));
+        assert!(moves.contains(&"Q1e1".to_string()));
+        // Only pieces which can legally move there count, so the pinned
+        // knight doesn't need telling apart
+        let moves = all_san(&state("4k3/8/8/8/1b6/8/3N4/4K1N1 w - - 0 1"));
+        assert!(moves.contains(&"Nf3".to_string()));
+    }
+
+    #[test]
+    fn test_pawn_moves() {
+        let state = state("3qk3/4P3/8/3pP3/8/8/8/4K3 w - d6 0 1");
+        let moves = all_san(&state);
+        assert!(moves.contains(&"exd6".to_string()));
+        assert!(moves.contains(&"exd8=Q+".to_string()));
+        assert!(moves.contains(&"exd8=N".to_string()));
+        assert!(moves.contains(&"e6".to_string()));
+        let mv = state.parse_san("exd8=Q+").unwrap();
+        assert!(mv.promotion() == Some(Piece::Queen) && mv.is_capture());
+        let mv = state.parse_san("exd8N").unwrap();
+        assert!(mv.promotion() == Some(Piece::Knight));
+        assert!(state.parse_san("exd6").unwrap().is_en_passant());
+    }
+
+    #[test]
+    fn test_castling() {
+        let state = state("r3k3/8/8/8/8/8/8/R3K2R w KQq - 0 1");
+        let moves = all_san(&state);
+        assert!(moves.contains(&"O-O".to_string()));
+        assert!(moves.contains(&"O-O-O".to_string()));
+        let mv = state.parse_san("O-O-O").unwrap();
+        assert!(mv.kind() == MoveKind::Castle(CastleSide::Queen));
+        assert!(state.parse_san("0-0").unwrap().kind() == MoveKind::Castle(CastleSide::King));
+        // The rook gives check from its castling destination
+        let state = self::state("3k4/8/8/8/8/8/8/R3K3 w Q - 0 1");
+        assert!(state.to_san(&state.parse_san("O-O-O").unwrap()) == "O-O-O+");
+    }
+
+    #[test]
+    fn test_check_and_mate() {
+        let state = state("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1");
+        let mate = state.parse_san("Ra8#").unwrap();
+        assert!(state.to_san(&mate) == "Ra8#");
+        let state = self::state("6k1/5pp1/8/8/8/8/5PPP/R5K1 w - - 0 1");
+        assert!(state.to_san(&state.parse_san("Ra8").unwrap()) == "Ra8+");
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let fens = [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        ];
+        for fen in fens.iter() {
+            let state = state(fen);
+            for mv in state.legal_moves().into_iter() {
+                let san = state.to_san(&mv);
+                assert!(state.parse_san(&san) == Ok(mv), "{} in {}", san, fen);
+            }
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        let state = state("1n2k3/8/5n2/8/8/8/8/4K3 b - - 0 1");
+        assert!(state.parse_san("Nd7") == Err(SanError::Ambiguous("Nd7".to_string())));
+        assert!(state.parse_san("Nc5") == Err(SanError::Illegal("Nc5".to_string())));
+        assert!(state.parse_san("Ke9") == Err(SanError::Invalid("Ke9".to_string())));
+        assert!(state.parse_san("Zd7") == Err(SanError::Invalid("Zd7".to_string())));
+        assert!(state.parse_san("") == Err(SanError::Invalid("".to_string())));
+        assert!(state.parse_san("Nd7=Q") == Err(SanError::Invalid("Nd7=Q".to_string())));
+        assert!(state.parse_san("O-O") == Err(SanError::Illegal("O-O".to_string())));
+        let state = self::state("4k3/P7/8/8/8/8/8/4K3 w - - 0 1");
+        assert!(state.parse_san("a8") == Err(SanError::Illegal("a8".to_string())));
+        assert!(state.parse_san("a8=K") == Err(SanError::Invalid("a8=K".to_string())));
+    }
+}
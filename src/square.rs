@@ -1,7 +1,11 @@
 //! Square constants and enumerations
 
+use std::convert::TryFrom;
+use std::error::Error;
 use std::fmt;
+use std::str::FromStr;
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum SquareColor {
     White,
     Black,
@@ -13,7 +17,7 @@ pub enum SquareColor {
 /// going right across the files. The enum below is formatted as though
 /// it were a chess board.
 #[rustfmt::skip]
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum Square {
     A8 = 56, B8 = 57, C8 = 58, D8 = 59, E8 = 60, F8 = 61, G8 = 62, H8 = 63,
     A7 = 48, B7 = 49, C7 = 50, D7 = 51, E7 = 52, F7 = 53, G7 = 54, H7 = 55,
@@ -30,89 +34,245 @@ impl fmt::Display for Square {
     }
 }
 impl Square {
-    pub fn color(&self) -> SquareColor {
+    /// The square on the given file and rank
+    pub const fn new(file: File, rank: Rank) -> Self {
+        SQUARES[rank as usize * 8 + file as usize]
+    }
+    /// The square with the given LRF index, if it's on the board
+    pub const fn from_index(index: u8) -> Option<Self> {
+        if index < 64 {
+            Some(SQUARES[index as usize])
+        } else {
+            None
+        }
+    }
+    /// The square's LRF index, from 0 for A1 to 63 for H8
+    pub const fn index(&self) -> u8 {
+        *self as u8
+    }
+    pub const fn file(&self) -> File {
+        FILES[self.index() as usize % 8]
+    }
+    pub const fn rank(&self) -> Rank {
+        RANKS[self.index() as usize / 8]
+    }
+    /// The square the given number of files and ranks away, if it's still on
+    /// the board
+    pub const fn offset(&self, files: i8, ranks: i8) -> Option<Self> {
+        let file = self.file() as i8 + files;
+        let rank = self.rank() as i8 + ranks;
+        if file < 0 || file > 7 || rank < 0 || rank > 7 {
+            None
+        } else {
+            Some(SQUARES[(rank * 8 + file) as usize])
+        }
+    }
+    /// The number of king moves between two squares (Chebyshev distance)
+    pub const fn distance(&self, other: &Self) -> u8 {
+        let files = self.file().distance(other.file());
+        let ranks = self.rank().distance(other.rank());
+        if files > ranks {
+            files
+        } else {
+            ranks
+        }
+    }
+    /// The number of rook steps between two squares (Manhattan distance)
+    pub const fn manhattan_distance(&self, other: &Self) -> u8 {
+        self.file().distance(other.file()) + self.rank().distance(other.rank())
+    }
+    /// The same square seen from the other side, mirroring ranks (A1 ↔ A8)
+    pub const fn flip_vertical(&self) -> Self {
+        SQUARES[(self.index() ^ 56) as usize]
+    }
+    /// The square mirrored across the board's centre line (A1 ↔ H1)
+    pub const fn flip_horizontal(&self) -> Self {
+        SQUARES[(self.index() ^ 7) as usize]
+    }
+    /// Squares alternate colour along both files and ranks, so the parity of
+    /// file + rank gives the colour, with A1 dark
+    pub const fn color(&self) -> SquareColor {
+        if (self.index() ^ (self.index() >> 3)) & 1 == 0 {
+            SquareColor::Black
+        } else {
+            SquareColor::White
+        }
+    }
+}
+impl TryFrom<u8> for Square {
+    type Error = SquareError;
+    fn try_from(index: u8) -> Result<Self, Self::Error> {
+        Self::from_index(index).ok_or(SquareError::Index(index))
+    }
+}
+/// Read a square name in either case, like `e4` or `E4`
+impl FromStr for Square {
+    type Err = SquareError;
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let mut chars = name.chars();
+        match (chars.next(), chars.next(), chars.next()) {
+            (Some(file), Some(rank), None) => {
+                match (
+                    File::from_char(file.to_ascii_lowercase()),
+                    Rank::from_char(rank),
+                ) {
+                    (Some(file), Some(rank)) => Ok(Self::new(file, rank)),
+                    _ => Err(SquareError::Invalid(name.to_string())),
+                }
+            }
+            _ => Err(SquareError::Invalid(name.to_string())),
+        }
+    }
+}
+
+/// Why a square couldn't be made
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SquareError {
+    /// The index is 64 or more
+    Index(u8),
+    /// The text isn't a square name
+    Invalid(String),
+}
+impl fmt::Display for SquareError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            // Rank 1
-            Square::A1 => SquareColor::Black,
-            Square::B1 => SquareColor::White,
-            Square::C1 => SquareColor::Black,
-            Square::D1 => SquareColor::White,
-            Square::E1 => SquareColor::Black,
-            Square::F1 => SquareColor::White,
-            Square::G1 => SquareColor::Black,
-            Square::H1 => SquareColor::White,
-            // Rank 2
-            Square::A2 => SquareColor::White,
-            Square::B2 => SquareColor::Black,
-            Square::C2 => SquareColor::White,
-            Square::D2 => SquareColor::Black,
-            Square::E2 => SquareColor::White,
-            Square::F2 => SquareColor::Black,
-            Square::G2 => SquareColor::White,
-            Square::H2 => SquareColor::Black,
-            // Rank 3
-            Square::A3 => SquareColor::Black,
-            Square::B3 => SquareColor::White,
-            Square::C3 => SquareColor::Black,
-            Square::D3 => SquareColor::White,
-            Square::E3 => SquareColor::Black,
-            Square::F3 => SquareColor::White,
-            Square::G3 => SquareColor::Black,
-            Square::H3 => SquareColor::White,
-            // Rank 4
-            Square::A4 => SquareColor::White,
-            Square::B4 => SquareColor::Black,
-            Square::C4 => SquareColor::White,
-            Square::D4 => SquareColor::Black,
-            Square::E4 => SquareColor::White,
-            Square::F4 => SquareColor::Black,
-            Square::G4 => SquareColor::White,
-            Square::H4 => SquareColor::Black,
-            // Rank 5
-            Square::A5 => SquareColor::Black,
-            Square::B5 => SquareColor::White,
-            Square::C5 => SquareColor::Black,
-            Square::D5 => SquareColor::White,
-            Square::E5 => SquareColor::Black,
-            Square::F5 => SquareColor::White,
-            Square::G5 => SquareColor::Black,
-            Square::H5 => SquareColor::White,
-            // Rank 6
-            Square::A6 => SquareColor::White,
-            Square::B6 => SquareColor::Black,
-            Square::C6 => SquareColor::White,
-            Square::D6 => SquareColor::Black,
-            Square::E6 => SquareColor::White,
-            Square::F6 => SquareColor::Black,
-            Square::G6 => SquareColor::White,
-            Square::H6 => SquareColor::Black,
-            // Rank 7
-            Square::A7 => SquareColor::Black,
-            Square::B7 => SquareColor::White,
-            Square::C7 => SquareColor::Black,
-            Square::D7 => SquareColor::White,
-            Square::E7 => SquareColor::Black,
-            Square::F7 => SquareColor::White,
-            Square::G7 => SquareColor::Black,
-            Square::H7 => SquareColor::White,
-            // Rank 8
-            Square::A8 => SquareColor::White,
-            Square::B8 => SquareColor::Black,
-            Square::C8 => SquareColor::White,
-            Square::D8 => SquareColor::Black,
-            Square::E8 => SquareColor::White,
-            Square::F8 => SquareColor::Black,
-            Square::G8 => SquareColor::White,
-            Square::H8 => SquareColor::Black,
+            Self::Index(index) => write!(f, "square index {} is off the board", index),
+            Self::Invalid(name) => write!(f, "invalid square {:?}", name),
+        }
+    }
+}
+impl Error for SquareError {}
+
+/// Files A to H, from White's left
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum File {
+    A,
+    B,
+    C,
+    D,
+    E,
+    F,
+    G,
+    H,
+}
+impl File {
+    /// The file from its index, 0 for A up to 7 for H
+    pub const fn from_index(index: u8) -> Option<Self> {
+        if index < 8 {
+            Some(FILES[index as usize])
+        } else {
+            None
+        }
+    }
+    /// The file from its lowercase letter
+    pub const fn from_char(letter: char) -> Option<Self> {
+        match letter {
+            'a'..='h' => Some(FILES[(letter as u8 - b'a') as usize]),
+            _ => None,
+        }
+    }
+    pub const fn index(self) -> u8 {
+        self as u8
+    }
+    /// The file's lowercase letter
+    pub const fn to_char(self) -> char {
+        (b'a' + self as u8) as char
+    }
+    /// The file mirrored across the centre line, A ↔ H
+    pub const fn flip(self) -> Self {
+        FILES[7 - self as usize]
+    }
+    /// The number of files between this and another
+    pub const fn distance(self, other: Self) -> u8 {
+        (self as u8).abs_diff(other as u8)
+    }
+}
+impl fmt::Display for File {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_char())
+    }
+}
+
+/// Ranks 1 to 8, from White's side of the board
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Rank {
+    First,
+    Second,
+    Third,
+    Fourth,
+    Fifth,
+    Sixth,
+    Seventh,
+    Eighth,
+}
+impl Rank {
+    /// The rank from its index, 0 for the first up to 7 for the eighth
+    pub const fn from_index(index: u8) -> Option<Self> {
+        if index < 8 {
+            Some(RANKS[index as usize])
+        } else {
+            None
         }
     }
+    /// The rank from its digit
+    pub const fn from_char(digit: char) -> Option<Self> {
+        match digit {
+            '1'..='8' => Some(RANKS[(digit as u8 - b'1') as usize]),
+            _ => None,
+        }
+    }
+    pub const fn index(self) -> u8 {
+        self as u8
+    }
+    /// The rank's digit
+    pub const fn to_char(self) -> char {
+        (b'1' + self as u8) as char
+    }
+    /// The rank seen from the other side, first ↔ eighth
+    pub const fn flip(self) -> Self {
+        RANKS[7 - self as usize]
+    }
+    /// The number of ranks between this and another
+    pub const fn distance(self, other: Self) -> u8 {
+        (self as u8).abs_diff(other as u8)
+    }
+}
+impl fmt::Display for Rank {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_char())
+    }
 }
 
+/// All files, from A to H
+pub const FILES: [File; 8] = [
+    File::A,
+    File::B,
+    File::C,
+    File::D,
+    File::E,
+    File::F,
+    File::G,
+    File::H,
+];
+
+/// All ranks, from first to eighth
+pub const RANKS: [Rank; 8] = [
+    Rank::First,
+    Rank::Second,
+    Rank::Third,
+    Rank::Fourth,
+    Rank::Fifth,
+    Rank::Sixth,
+    Rank::Seventh,
+    Rank::Eighth,
+];
+
 /// Precalculated Square (index) positions (LRF bitboard positions)
 ///
 /// Shift is pretty cheap, but no reason not to stash these rather than
 /// calculating on demand.
 #[derive(Clone, Copy, Debug)]
+#[repr(u64)]
 pub enum SquarePosition {
     // Don't worry, I did this with vim macros :)
     A1 = 1 << Square::A1 as u8,
@@ -332,3 +492,68 @@ pub const SQUARE_POSITIONS: [SquarePosition; 64] = [
     SquarePosition::H7,
     SquarePosition::H8,
 ];
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_file_and_rank() {
+        assert!(Square::E4.file() == File::E);
+        assert!(Square::E4.rank() == Rank::Fourth);
+        assert!(Square::new(File::H, Rank::Eighth) == Square::H8);
+        assert!(SQUARES
+            .iter()
+            .all(|square| Square::new(square.file(), square.rank()) == *square));
+        assert!(File::from_char('c') == Some(File::C));
+        assert!(File::from_char('C').is_none());
+        assert!(Rank::from_char('9').is_none());
+        assert!(File::G.to_string() == "g" && Rank::Second.to_string() == "2");
+    }
+
+    #[test]
+    fn test_parse() {
+        assert!("e4".parse::<Square>() == Ok(Square::E4));
+        assert!("E4".parse::<Square>() == Ok(Square::E4));
+        assert!("h8".parse::<Square>() == Ok(Square::H8));
+        for invalid in ["", "e", "e9", "i4", "e44", "4e"].iter() {
+            assert!(invalid.parse::<Square>() == Err(SquareError::Invalid(invalid.to_string())));
+        }
+        assert!(Square::try_from(0) == Ok(Square::A1));
+        assert!(Square::try_from(63) == Ok(Square::H8));
+        assert!(Square::try_from(64) == Err(SquareError::Index(64)));
+    }
+
+    #[test]
+    fn test_offset_and_distance() {
+        assert!(Square::E4.offset(1, 2) == Some(Square::F6));
+        assert!(Square::A1.offset(-1, 0).is_none());
+        assert!(Square::H8.offset(0, 1).is_none());
+        assert!(Square::A1.distance(&Square::H8) == 7);
+        assert!(Square::E4.distance(&Square::F6) == 2);
+        assert!(Square::A1.manhattan_distance(&Square::H8) == 14);
+        assert!(Square::E4.manhattan_distance(&Square::E4) == 0);
+    }
+
+    #[test]
+    fn test_mirroring() {
+        assert!(Square::A1.flip_vertical() == Square::A8);
+        assert!(Square::E2.flip_vertical() == Square::E7);
+        assert!(Square::A1.flip_horizontal() == Square::H1);
+        assert!(Square::D5.flip_horizontal() == Square::E5);
+        assert!(File::B.flip() == File::G && Rank::Third.flip() == Rank::Sixth);
+    }
+
+    #[test]
+    fn test_color() {
+        assert!(Square::A1.color() == SquareColor::Black);
+        assert!(Square::H1.color() == SquareColor::White);
+        assert!(Square::D1.color() == SquareColor::White);
+        assert!(Square::D8.color() == SquareColor::Black);
+        assert!(Square::H8.color() == SquareColor::Black);
+        // Neighbours always differ
+        assert!(SQUARES.iter().all(|square| square
+            .offset(1, 0)
+            .is_none_or(|right| right.color() != square.color())));
+    }
+}
@@ -1,6 +1,6 @@
 //! Common traits
 
-use crate::board::Move;
+use crate::moves::Move;
 use crate::square::Square;
 
 /// Something that can indicate whether it's occupied
@@ -0,0 +1,280 @@
+//! Zobrist hashing
+//!
+//! A position's key is the XOR of a random number for each piece on its
+//! square, for black to move, for each castling right and for the en passant
+//! file. A move changes only a few of these, so a key is updated by XORing
+//! the changes out and in rather than recomputed. The numbers are generated
+//! at compile time from a fixed seed, so keys are the same on every run and
+//! may be stored.
+
+use crate::attacks::pawn_attacks;
+use crate::board::{Board, Color, Piece, ALL_PIECES};
+use crate::castling::{CastleSide, CastlingRights};
+use crate::moves::{Move, MoveKind};
+use crate::square::Square;
+
+const KEYS: Keys = Keys::generate(0x5EED_CAFE_F00D_D00D);
+
+/// The random numbers behind every key
+struct Keys {
+    pieces: [[[u64; 64]; 6]; 2],
+    black_to_move: u64,
+    castling: [u64; 4],
+    en_passant: [u64; 8],
+}
+impl Keys {
+    const fn generate(seed: u64) -> Self {
+        let mut state = seed;
+        let mut pieces = [[[0; 64]; 6]; 2];
+        let mut color = 0;
+        while color < 2 {
+            let mut piece = 0;
+            while piece < 6 {
+                let mut square = 0;
+                while square < 64 {
+                    state = splitmix(state);
+                    pieces[color][piece][square] = mix(state);
+                    square += 1;
+                }
+                piece += 1;
+            }
+            color += 1;
+        }
+        state = splitmix(state);
+        let black_to_move = mix(state);
+        let mut castling = [0; 4];
+        let mut i = 0;
+        while i < 4 {
+            state = splitmix(state);
+            castling[i] = mix(state);
+            i += 1;
+        }
+        let mut en_passant = [0; 8];
+        let mut i = 0;
+        while i < 8 {
+            state = splitmix(state);
+            en_passant[i] = mix(state);
+            i += 1;
+        }
+        Self {
+            pieces,
+            black_to_move,
+            castling,
+            en_passant,
+        }
+    }
+}
+
+/// Advance the SplitMix64 generator
+const fn splitmix(state: u64) -> u64 {
+    state.wrapping_add(0x9E37_79B9_7F4A_7C15)
+}
+
+/// Scramble a SplitMix64 state into its output
+const fn mix(state: u64) -> u64 {
+    let z = (state ^ (state >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    let z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// The key for a piece on a square
+pub const fn piece_key(color: Color, piece: Piece, square: &Square) -> u64 {
+    KEYS.pieces[color as usize][piece as usize][*square as usize]
+}
+
+/// The key for the side to move, which is only included when it's black
+pub const fn side_key(turn: Color) -> u64 {
+    match turn {
+        Color::White => 0,
+        Color::Black => KEYS.black_to_move,
+    }
+}
+
+/// The key for every castling right held
+///
+/// Rights are keyed by side and wing, not by the rook's square.
+pub const fn castling_key(rights: &CastlingRights) -> u64 {
+    let rights = [
+        rights.has(Color::White, CastleSide::King),
+        rights.has(Color::White, CastleSide::Queen),
+        rights.has(Color::Black, CastleSide::King),
+        rights.has(Color::Black, CastleSide::Queen),
+    ];
+    let mut key = 0;
+    let mut i = 0;
+    while i < 4 {
+        if rights[i] {
+            key ^= KEYS.castling[i];
+        }
+        i += 1;
+    }
+    key
+}
+
+/// The key for an en passant target
+///
+/// The file is only included when the side to move has a pawn which could
+/// capture there, so positions which differ only by an unusable target
+/// still share a key, as they count as the same position for repetitions.
+pub const fn en_passant_key(board: &Board, turn: Color, target: Option<Square>) -> u64 {
+    let target = match target {
+        Some(target) => target,
+        None => return 0,
+    };
+    let pawns = board.pieces(turn).piece_board(Piece::Pawn).board();
+    if pawn_attacks(&target, turn.opponent())
+        .intersection(&pawns)
+        .is_empty()
+    {
+        0
+    } else {
+        KEYS.en_passant[target.file() as usize]
+    }
+}
+
+/// The change in a board's key when a move is applied to it
+pub fn move_key(board: &Board, mv: &Move) -> u64 {
+    let from = mv.from();
+    let (piece, color) = match board.occupant(&from) {
+        Some(occupant) => occupant,
+        None => return 0,
+    };
+    if let MoveKind::Castle(side) = mv.kind() {
+        return piece_key(color, Piece::King, &from)
+            ^ piece_key(color, Piece::King, &side.king_destination(&from))
+            ^ piece_key(color, Piece::Rook, &mv.to())
+            ^ piece_key(color, Piece::Rook, &side.rook_destination(&from));
+    }
+    let captured_square = mv.captured_square();
+    let captured = board
+        .occupant(&captured_square)
+        .map_or(0, |(piece, color)| {
+            piece_key(color, piece, &captured_square)
+        });
+    let placed = mv.promotion().unwrap_or(piece);
+    piece_key(color, piece, &from) ^ piece_key(color, placed, &mv.to()) ^ captured
+}
+
+impl Board {
+    /// The key for the pieces on the board, computed from scratch
+    pub const fn zobrist(&self) -> u64 {
+        let mut key = 0;
+        let colors = [Color::White, Color::Black];
+        let mut c = 0;
+        while c < 2 {
+            let mut p = 0;
+            while p < 6 {
+                let piece_board = self.pieces(colors[c]).piece_board(ALL_PIECES[p]);
+                let mut positions = piece_board.board().positions();
+                while positions != 0 {
+                    let square = positions.trailing_zeros() as usize;
+                    key ^= KEYS.pieces[c][p][square];
+                    positions &= positions - 1;
+                }
+                p += 1;
+            }
+            c += 1;
+        }
+        key
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::game_state::GameState;
+
+    #[test]
+    fn test_keys_distinct() {
+        let mut keys = KEYS
+            .pieces
+            .iter()
+            .flatten()
+            .flatten()
+            .chain(KEYS.castling.iter())
+            .chain(KEYS.en_passant.iter())
+            .copied()
+            .collect::<Vec<u64>>();
+        keys.push(KEYS.black_to_move);
+        let count = keys.len();
+        keys.sort_unstable();
+        keys.dedup();
+        assert!(keys.len() == count);
+        assert!(keys.iter().all(|key| *key != 0));
+    }
+
+    #[test]
+    fn test_transpositions() {
+        let start = GameState::fresh_game();
+        let mut a = start.clone();
+        a.play(&["Nf3", "Nf6", "Nc3"]);
+        let mut b = start.clone();
+        b.play(&["Nc3", "Nf6", "Nf3"]);
+        assert!(a.zobrist() == b.zobrist());
+        assert!(a.board().zobrist() == b.board().zobrist());
+        let mut back = start.clone();
+        back.play(&["Nf3", "Nf6", "Ng1", "Ng8"]);
+        assert!(back.zobrist() == start.zobrist());
+        let mut black = start.clone();
+        black.play(&["Nf3", "Nf6", "Ng1"]);
+        assert!(
+            black.zobrist() ^ side_key(Color::Black)
+                == start.zobrist()
+                    ^ piece_key(Color::Black, Piece::Knight, &Square::F6)
+                    ^ piece_key(Color::Black, Piece::Knight, &Square::G8)
+        );
+    }
+
+    #[test]
+    fn test_castling_and_en_passant() {
+        let key = |fen: &str| GameState::from_fen(fen).unwrap().zobrist();
+        let all = key("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+        assert!(all != key("r3k2r/8/8/8/8/8/8/R3K2R w KQk - 0 1"));
+        assert!(all != key("r3k2r/8/8/8/8/8/8/R3K2R w - - 0 1"));
+        // Rights lost by moving the rook there and back
+        let mut moved = GameState::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        moved.play(&["Rh2", "Rh7", "Rh1", "Rh8"]);
+        assert!(moved.zobrist() == key("r3k2r/8/8/8/8/8/8/R3K2R w Qq - 4 3"));
+
+        // Only a target that can be captured on counts
+        assert!(key("4k3/8/8/8/4P3/8/8/4K3 b - e3 0 1") == key("4k3/8/8/8/4P3/8/8/4K3 b - - 0 1"));
+        assert!(
+            key("4k3/8/8/8/3pP3/8/8/4K3 b - e3 0 1") != key("4k3/8/8/8/3pP3/8/8/4K3 b - - 0 1")
+        );
+        let mut pushed = GameState::fresh_game();
+        pushed.play(&["e4"]);
+        assert!(
+            pushed.zobrist() == key("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1")
+        );
+    }
+
+    #[test]
+    fn test_incremental_updates() {
+        // Every move of every kind, checked against a full recompute
+        let fens = [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9",
+        ];
+        for fen in fens.iter() {
+            let state = GameState::from_fen(fen).unwrap();
+            for mv in state.legal_moves().iter() {
+                let next = state.apply_move(mv);
+                let full = GameState::new(next.board().clone(), next.turn(), *next.meta());
+                assert!(next.zobrist() == full.zobrist(), "{} {:?}", fen, mv);
+                for reply in next.legal_moves().iter() {
+                    let after = next.apply_move(reply);
+                    let full = GameState::new(after.board().clone(), after.turn(), *after.meta());
+                    assert!(
+                        after.zobrist() == full.zobrist(),
+                        "{} {:?} {:?}",
+                        fen,
+                        mv,
+                        reply
+                    );
+                }
+            }
+        }
+    }
+}